@@ -0,0 +1,260 @@
+//! `.st/cache` 中缓存的命令结果
+//!
+//! 输入 (插件实际执行的命令行 以及项目中所有文件的内容) 没有变化, 并且上次执行成功时 跳过命令
+//!
+//! 输入包括:
+//! * 项目中的所有文件 (包括 `.cargo/config.toml`、`.eslintrc` 等隐藏文件),
+//!   只跳过 `.git`、`.st` 以及 `target`、`node_modules` 等生成的目录
+//! * 上级目录 (直到 git 仓库的根目录) 中的项目配置 以及锁文件, 例如 workspace 的 `Cargo.lock`
+//! * 项目外的 Cargo `path` 依赖
+//!
+//! 哈希使用 SHA-256, 不同版本的 st 以及 Rust 计算的结果相同
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use crate::public::{Action, Context, RecordingRunner, StError, StResult};
+use crate::utils;
+
+/// 缓存所在的目录 (相对于项目根目录)
+const CACHE_DIR: &str = ".st/cache";
+
+/// 上级目录中 会影响项目的配置文件 以及锁文件
+const WORKSPACE_FILES: [&str; 8] = [
+    "Cargo.toml",
+    "Cargo.lock",
+    "package.json",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "pyproject.toml",
+    "poetry.lock",
+];
+
+/// `clean` 之后 删除缓存, 否则 `build` 会跳过, 不生成删除了的文件
+pub fn clear(ctx: &Context) {
+    let dir = ctx.path(CACHE_DIR);
+    if !dir.exists() {
+        return;
+    }
+    if ctx.dry_run {
+        println!("[dry-run] remove {}", dir.display());
+        return;
+    }
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        eprintln!("删除缓存 {} 失败: {}", dir.display(), e);
+    }
+}
+
+/// 可以缓存结果的命令, 它们不会修改源代码
+fn is_cacheable(action: &Action) -> bool {
+    matches!(action, Action::Build | Action::Lint | Action::Test)
+}
+
+/// 使用插件 `plugin` 执行 `action`, 输入和上次成功时相同时 跳过, 返回 `true`
+///
+/// `run` 使用传入的上下文执行命令, 计算输入时 会使用 `RecordingRunner` 调用它 获取实际执行的命令行
+pub fn run_cached<F>(plugin: &str, action: &Action, ctx: &Context, run: F) -> Result<bool, StError>
+where
+    F: Fn(&Context) -> StResult,
+{
+    // 只处理部分文件时 结果不能代表整个项目
+    if !is_cacheable(action) || ctx.dry_run || ctx.files.is_some() {
+        return run(ctx).map(|_| false);
+    }
+    let hash = match input_hash(ctx, &run) {
+        Some(h) => h,
+        None => return run(ctx).map(|_| false),
+    };
+
+    let file = ctx
+        .path(CACHE_DIR)
+        .join(format!("{}-{}", plugin, action.name()));
+    if fs::read_to_string(&file).ok().as_deref() == Some(hash.as_str()) {
+        println!("{} {}: cached: pass", plugin, action.name());
+        return Ok(true);
+    }
+
+    run(ctx)?;
+    if let Err(e) = save(&file, &hash) {
+        eprintln!("{}", e);
+    }
+    Ok(false)
+}
+
+/// 命令行 以及所有输入文件的 SHA-256
+///
+/// 插件没有执行任何命令时 返回 `None`
+fn input_hash<F>(ctx: &Context, run: &F) -> Option<String>
+where
+    F: Fn(&Context) -> StResult,
+{
+    let recorder = Arc::new(RecordingRunner::new());
+    let probe = Context {
+        verbose: 0,
+        ..ctx.clone().with_runner(recorder.clone())
+    };
+    run(&probe).ok()?;
+    let commands = recorder.commands();
+    if commands.is_empty() {
+        return None;
+    }
+
+    let mut hasher = Sha256::new();
+    for cmd in &commands {
+        // 工具的实际路径, 例如切换了 Rust 工具链
+        let program = cmd.program.to_str().and_then(|p| ctx.exec_path(p).ok());
+        write_field(&mut hasher, format!("{:?}", program).as_bytes());
+        write_field(&mut hasher, cmd.to_string().as_bytes());
+        write_field(&mut hasher, cmd.cwd.to_string_lossy().as_bytes());
+        write_field(&mut hasher, format!("{:?}", cmd.envs).as_bytes());
+    }
+    for path in input_files(&ctx.root) {
+        write_field(&mut hasher, path.to_string_lossy().as_bytes());
+        write_field(&mut hasher, &read_input(&path)?);
+    }
+    let hash = hasher.finalize();
+    Some(hash.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// 输入文件的内容
+///
+/// 符号链接指向目录 或者不存在的文件 (例如 emacs 的 `.#main.rs`) 时, 使用链接的目标路径.
+/// 读取失败时 打印警告 并返回 `None`, 本次不使用缓存
+fn read_input(path: &Path) -> Option<Vec<u8>> {
+    let is_link = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+    let ret = match fs::read(path) {
+        Err(_) if is_link => {
+            fs::read_link(path).map(|t| t.to_string_lossy().into_owned().into_bytes())
+        }
+        ret => ret,
+    };
+    match ret {
+        Ok(data) => Some(data),
+        Err(e) => {
+            eprintln!("读取 {} 失败: {}, 不使用缓存", path.display(), e);
+            None
+        }
+    }
+}
+
+/// 写入长度和内容, 避免相邻的字段拼接后相同
+fn write_field(hasher: &mut Sha256, data: &[u8]) {
+    hasher.update((data.len() as u64).to_le_bytes());
+    hasher.update(data);
+}
+
+/// 影响命令结果的所有文件, 按路径排序
+fn input_files(root: &Path) -> BTreeSet<PathBuf> {
+    let mut files = BTreeSet::new();
+    collect_files(root, &mut files);
+
+    // 上级目录中的 workspace 配置 以及锁文件, 不越过 git 仓库
+    let mut manifests = vec![root.join("Cargo.toml")];
+    for dir in root.ancestors().skip(1) {
+        for name in &WORKSPACE_FILES {
+            let path = dir.join(name);
+            if path.is_file() {
+                files.insert(path);
+            }
+        }
+        manifests.push(dir.join("Cargo.toml"));
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    // 项目外的 path 依赖
+    let canonical_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let mut visited = BTreeSet::new();
+    while let Some(manifest) = manifests.pop() {
+        for dep in path_dependencies(&manifest) {
+            let dep = fs::canonicalize(&dep).unwrap_or(dep);
+            if dep.starts_with(&canonical_root) || !visited.insert(dep.clone()) {
+                continue;
+            }
+            collect_files(&dep, &mut files);
+            manifests.push(dep.join("Cargo.toml"));
+        }
+    }
+    files
+}
+
+/// `dir` 中的所有文件, 跳过 `.git`、`.st` 以及生成的目录
+fn collect_files(dir: &Path, files: &mut BTreeSet<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(d) => d,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        // 不跟随指向目录的符号链接, 避免循环, 见 `read_input`
+        match entry.file_type() {
+            Ok(t) if t.is_dir() => {
+                if name != ".st" && !utils::IGNORE_DIRS.contains(&name.as_ref()) {
+                    collect_files(&path, files);
+                }
+            }
+            Ok(_) => {
+                files.insert(path);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// `Cargo.toml` 中 `path` 依赖的目录, 包括 `[workspace.dependencies]` 以及 `[target.*]` 中的依赖
+fn path_dependencies(manifest: &Path) -> Vec<PathBuf> {
+    let value = match fs::read_to_string(manifest)
+        .ok()
+        .and_then(|s| s.parse::<toml::Value>().ok())
+    {
+        Some(v) => v,
+        None => return vec![],
+    };
+    let dir = manifest.parent().unwrap_or(manifest);
+
+    let mut tables = vec![&value];
+    if let Some(workspace) = value.get("workspace") {
+        tables.push(workspace);
+    }
+    if let Some(targets) = value.get("target").and_then(|t| t.as_table()) {
+        tables.extend(targets.values());
+    }
+    let mut deps = vec![];
+    for table in tables {
+        for key in &["dependencies", "dev-dependencies", "build-dependencies"] {
+            let entries = match table.get(*key).and_then(|d| d.as_table()) {
+                Some(d) => d,
+                None => continue,
+            };
+            deps.extend(
+                entries
+                    .values()
+                    .filter_map(|d| d.get("path")?.as_str())
+                    .map(|p| dir.join(p)),
+            );
+        }
+    }
+    deps
+}
+
+/// 保存成功时的哈希
+fn save(file: &Path, hash: &str) -> Result<(), StError> {
+    let dir = file.parent().unwrap_or(file);
+    fs::create_dir_all(dir)
+        .map_err(|e| StError::io(format!("创建缓存目录 {} 失败", dir.display()), e))?;
+    // 缓存不需要提交到 git
+    let ignore = dir.join(".gitignore");
+    if !ignore.exists() {
+        fs::write(&ignore, "*\n")
+            .map_err(|e| StError::io(format!("写入 {} 失败", ignore.display()), e))?;
+    }
+    fs::write(file, hash).map_err(|e| StError::io(format!("写入缓存 {} 失败", file.display()), e))
+}
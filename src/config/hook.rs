@@ -0,0 +1,76 @@
+use serde::Deserialize;
+
+use super::{CommandLine, StConfig};
+
+/// 后置钩子的执行条件
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookWhen {
+    /// 命令成功时执行
+    #[default]
+    Success,
+    /// 命令失败时执行
+    Failure,
+    /// 总是执行
+    Always,
+}
+
+impl HookWhen {
+    /// 命令的执行结果为 `success` 时 是否执行钩子
+    pub fn matches(self, success: bool) -> bool {
+        match self {
+            HookWhen::Success => success,
+            HookWhen::Failure => !success,
+            HookWhen::Always => true,
+        }
+    }
+}
+
+/// `[hooks]` 中的单个钩子
+#[derive(Debug)]
+pub struct HookConfig {
+    pub command: Vec<String>,
+    /// 执行条件, 只对后置钩子有效
+    pub when: HookWhen,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawHookConfig {
+    Command(CommandLine),
+    Detail {
+        command: CommandLine,
+        #[serde(default)]
+        when: HookWhen,
+    },
+}
+
+impl<'de> Deserialize<'de> for HookConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(match RawHookConfig::deserialize(deserializer)? {
+            RawHookConfig::Command(cmd) => HookConfig {
+                command: cmd.into_args()?,
+                when: HookWhen::default(),
+            },
+            RawHookConfig::Detail { command, when } => HookConfig {
+                command: command.into_args()?,
+                when,
+            },
+        })
+    }
+}
+
+impl StConfig {
+    /// `action` 命令的前置钩子 (`pre_<action>`)
+    pub fn pre_hook(&self, action: &str) -> Option<&HookConfig> {
+        self.hooks.get(&format!("pre_{}", action))
+    }
+
+    /// `action` 命令的后置钩子 (`post_<action>`)
+    pub fn post_hook(&self, action: &str) -> Option<&HookConfig> {
+        self.hooks.get(&format!("post_{}", action))
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::public::{StError, StTrait};
+
+mod hook;
+mod task;
+
+pub use hook::{HookConfig, HookWhen};
+pub use task::TaskConfig;
+
+/// 独立的配置文件名称
+const CONFIG_FILE: &str = "st.toml";
+
+/// 内置的别名, 配置文件中的同名别名 会覆盖它们
+const BUILTIN_ALIASES: [(&str, &[&str]); 1] = [("ci", &["format --check", "lint", "test"])];
+
+/// 项目配置
+///
+/// 按以下顺序查找, 使用第一个找到的配置:
+///
+/// * `st.toml`
+/// * `pyproject.toml` 中的 `[tool.st]`
+/// * `Cargo.toml` 中的 `[package.metadata.st]`
+///
+/// 例如:
+///
+/// ```toml
+/// # 只运行这些插件 (命令行的 `--only` 会替换它)
+/// only = ["cargo", "poetry"]
+/// # 不运行这些插件 (会和命令行的 `--skip` 合并)
+/// skip = ["django"]
+/// # 插件的执行顺序, 没有列出的插件按默认顺序排在后面
+/// order = ["poetry", "cargo"]
+/// # `--recursive` 时运行的子项目, 为空时自动查找
+/// members = ["backend", "frontend"]
+///
+/// [aliases]
+/// # `st check` 依次执行这些命令, 命令名称之后的参数 会透传给插件
+/// check = ["format --check", "lint", "test -- --nocapture"]
+///
+/// [hooks]
+/// # 在 `st build` 之前执行, 失败时不再执行 build
+/// pre_build = "cargo sqlx prepare"
+/// # 在 `st publish` 之后执行, when 可以为 success (默认)、failure、always
+/// post_publish = { command = "notify-send 发布完成", when = "success" }
+///
+/// [tasks]
+/// # `st x proto`
+/// proto = "protoc --rust_out src proto/api.proto"
+///
+/// [tasks.docs]
+/// command = ["mkdocs", "build"]
+/// description = "生成文档"
+/// cwd = "docs"
+/// env = { SITE_URL = "https://example.com" }
+/// depends_on = ["proto"]
+///
+/// [plugins.cargo]
+/// # 直接覆盖整个命令
+/// lint = "cargo clippy --all-targets -- -D warnings"
+///
+/// [plugins.poetry.format]
+/// command = ["poetry", "run", "ruff", "format"]
+///
+/// [plugins.cargo.test]
+/// # 在默认命令的末尾追加参数
+/// args = ["--all-features"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct StConfig {
+    /// 只运行这些插件, 为空时不限制
+    #[serde(default)]
+    pub only: Vec<String>,
+    /// 不运行这些插件
+    #[serde(default)]
+    pub skip: Vec<String>,
+    /// 插件的执行顺序
+    #[serde(default)]
+    pub order: Vec<String>,
+    /// 子项目的目录 (相对于项目根目录)
+    #[serde(default)]
+    pub members: Vec<String>,
+    /// 插件名称 -> 命令名称 -> 命令配置
+    #[serde(default)]
+    pub plugins: HashMap<String, HashMap<String, ActionConfig>>,
+    /// 别名 -> 依次执行的命令 (已经拆分为参数)
+    #[serde(default, deserialize_with = "deserialize_aliases")]
+    pub aliases: HashMap<String, Vec<Vec<String>>>,
+    /// 钩子名称 (`pre_<action>`、`post_<action>`) -> 钩子
+    #[serde(default)]
+    pub hooks: HashMap<String, HookConfig>,
+    /// 任务名称 -> 任务, 通过 `st x <task>` 执行
+    #[serde(default)]
+    pub tasks: BTreeMap<String, TaskConfig>,
+    /// 在 `.st/cache` 中缓存 build、lint、test 的结果, 输入没有变化时 跳过
+    #[serde(default)]
+    pub cache: bool,
+}
+
+/// 单个命令的配置
+#[derive(Debug, Default)]
+pub struct ActionConfig {
+    /// 替换插件默认执行的命令
+    pub command: Option<Vec<String>>,
+    /// 追加到命令末尾的参数 (在用户透传的参数之前)
+    pub args: Vec<String>,
+}
+
+/// 命令行 可以写成字符串 也可以写成数组
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CommandLine {
+    Line(String),
+    Args(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawActionConfig {
+    Command(CommandLine),
+    Detail {
+        command: Option<CommandLine>,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+impl CommandLine {
+    fn into_args<E: serde::de::Error>(self) -> Result<Vec<String>, E> {
+        let args = match self {
+            CommandLine::Line(s) => shell_words::split(s.as_str())
+                .map_err(|e| E::custom(format!("解析命令 `{}` 失败: {}", s, e)))?,
+            CommandLine::Args(v) => v,
+        };
+        if args.is_empty() {
+            return Err(E::custom("命令不能为空"));
+        }
+        Ok(args)
+    }
+}
+
+impl<'de> Deserialize<'de> for ActionConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(match RawActionConfig::deserialize(deserializer)? {
+            RawActionConfig::Command(cmd) => ActionConfig {
+                command: Some(cmd.into_args()?),
+                args: vec![],
+            },
+            RawActionConfig::Detail { command, args } => ActionConfig {
+                command: command.map(CommandLine::into_args).transpose()?,
+                args,
+            },
+        })
+    }
+}
+
+fn deserialize_aliases<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, Vec<Vec<String>>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    HashMap::<String, Vec<CommandLine>>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, steps)| {
+            let steps = steps
+                .into_iter()
+                .map(CommandLine::into_args)
+                .collect::<Result<_, _>>()?;
+            Ok((name, steps))
+        })
+        .collect()
+}
+
+impl StConfig {
+    /// 从 `dir` 目录加载配置
+    ///
+    /// 没有配置时 返回默认配置
+    pub fn load(dir: &Path) -> Result<Self, StError> {
+        if dir.join(CONFIG_FILE).exists() {
+            let s = read_file(dir, CONFIG_FILE)?;
+            return toml::from_str(s.as_str()).map_err(|e| StError::bad_manifest(CONFIG_FILE, e));
+        }
+
+        let sections: [(&str, &[&str]); 2] = [
+            ("pyproject.toml", &["tool", "st"]),
+            ("Cargo.toml", &["package", "metadata", "st"]),
+        ];
+        for (file, path) in sections.iter() {
+            if !dir.join(file).exists() {
+                continue;
+            }
+            let v = toml::from_str::<toml::Value>(read_file(dir, file)?.as_str())
+                .map_err(|e| StError::bad_manifest(file, e))?;
+
+            let section = path.iter().try_fold(&v, |v, key| v.get(key));
+            if let Some(section) = section {
+                return section
+                    .clone()
+                    .try_into()
+                    .map_err(|e| StError::bad_manifest(file, format!("st 配置无效: {}", e)));
+            }
+        }
+
+        Ok(Self::default())
+    }
+
+    /// 按 `only`、`skip` 过滤插件, 并按 `order` 排序
+    pub fn select_plugins<'a>(&self, plugins: &'a [Box<dyn StTrait>]) -> Vec<&'a dyn StTrait> {
+        let names: Vec<String> = plugins.iter().map(|p| p.name()).collect();
+        self.only
+            .iter()
+            .chain(self.skip.iter())
+            .chain(self.order.iter())
+            .filter(|n| !names.contains(n))
+            .for_each(|n| eprintln!("未知的插件: {}, 可用的插件: {}", n, names.join(", ")));
+
+        let mut selected: Vec<&dyn StTrait> = plugins
+            .iter()
+            .map(|p| p.as_ref())
+            .filter(|p| self.only.is_empty() || self.only.contains(&p.name()))
+            .filter(|p| !self.skip.contains(&p.name()))
+            .collect();
+
+        // 没有在 order 中的插件 排在最后, sort_by_key 是稳定排序
+        selected.sort_by_key(|p| {
+            self.order
+                .iter()
+                .position(|n| *n == p.name())
+                .unwrap_or(self.order.len())
+        });
+        selected
+    }
+
+    /// 别名 `name` 对应的命令, 包括内置的别名 (例如 `ci`)
+    pub fn alias(&self, name: &str) -> Option<Vec<Vec<String>>> {
+        if let Some(steps) = self.aliases.get(name) {
+            return Some(steps.clone());
+        }
+        BUILTIN_ALIASES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, steps)| {
+                steps
+                    .iter()
+                    .map(|s| s.split_whitespace().map(String::from).collect())
+                    .collect()
+            })
+    }
+
+    /// 所有别名的名称 (包括内置的别名), 按名称排序
+    pub fn alias_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .aliases
+            .keys()
+            .cloned()
+            .chain(BUILTIN_ALIASES.iter().map(|(n, _)| n.to_string()))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// 获取 插件 `plugin` 的 `action` 命令的配置
+    pub fn action(&self, plugin: &str, action: &str) -> Option<&ActionConfig> {
+        self.plugins.get(plugin).and_then(|v| v.get(action))
+    }
+}
+
+fn read_file(dir: &Path, file: &str) -> Result<String, StError> {
+    fs::read_to_string(dir.join(file)).map_err(|e| StError::io(format!("读取 {} 失败", file), e))
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use super::{CommandLine, StConfig};
+use crate::public::StError;
+
+/// `[tasks]` 中的单个任务
+#[derive(Debug, Default)]
+pub struct TaskConfig {
+    /// 执行的命令, 为空时 只执行依赖的任务
+    pub command: Option<Vec<String>>,
+    /// 说明, `st x --list` 时显示
+    pub description: Option<String>,
+    /// 工作目录 (相对于项目根目录), 默认为项目根目录
+    pub cwd: Option<PathBuf>,
+    /// 额外的环境变量
+    pub env: BTreeMap<String, String>,
+    /// 在这个任务之前 需要执行的任务
+    pub depends_on: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawTaskConfig {
+    Command(CommandLine),
+    Detail {
+        command: Option<CommandLine>,
+        description: Option<String>,
+        cwd: Option<PathBuf>,
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+        #[serde(default)]
+        depends_on: Vec<String>,
+    },
+}
+
+impl<'de> Deserialize<'de> for TaskConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(match RawTaskConfig::deserialize(deserializer)? {
+            RawTaskConfig::Command(cmd) => TaskConfig {
+                command: Some(cmd.into_args()?),
+                ..TaskConfig::default()
+            },
+            RawTaskConfig::Detail {
+                command,
+                description,
+                cwd,
+                env,
+                depends_on,
+            } => TaskConfig {
+                command: command.map(CommandLine::into_args).transpose()?,
+                description,
+                cwd,
+                env,
+                depends_on,
+            },
+        })
+    }
+}
+
+/// 任务在遍历时的状态
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    /// 正在遍历它的依赖
+    Visiting,
+    Done,
+}
+
+impl StConfig {
+    /// 执行任务 `name` 时 需要依次执行的任务, 依赖在前, `name` 在最后
+    ///
+    /// 每个任务只执行一次, 任务不存在 或者存在循环依赖时 返回错误
+    pub fn task_plan(&self, name: &str) -> Result<Vec<String>, StError> {
+        if !self.tasks.contains_key(name) {
+            return Err(StError::UnknownTask(name.to_string()));
+        }
+        let mut state = BTreeMap::new();
+        let mut plan = vec![];
+        let mut path = vec![];
+        self.visit_task(name, &mut state, &mut path, &mut plan)?;
+        Ok(plan)
+    }
+
+    fn visit_task(
+        &self,
+        name: &str,
+        state: &mut BTreeMap<String, Visit>,
+        path: &mut Vec<String>,
+        plan: &mut Vec<String>,
+    ) -> Result<(), StError> {
+        match state.get(name) {
+            Some(Visit::Done) => return Ok(()),
+            Some(Visit::Visiting) => {
+                let start = path.iter().position(|n| n == name).unwrap_or(0);
+                let mut cycle = path[start..].to_vec();
+                cycle.push(name.to_string());
+                return Err(StError::bad_manifest(
+                    "st 配置",
+                    format!("任务存在循环依赖: {}", cycle.join(" -> ")),
+                ));
+            }
+            None => {}
+        }
+
+        let task = self.tasks.get(name).ok_or_else(|| {
+            StError::bad_manifest(
+                "st 配置",
+                format!(
+                    "任务 {} 依赖的任务 {} 不存在",
+                    path.last().map(String::as_str).unwrap_or_default(),
+                    name
+                ),
+            )
+        })?;
+
+        state.insert(name.to_string(), Visit::Visiting);
+        path.push(name.to_string());
+        for dep in &task.depends_on {
+            self.visit_task(dep, state, path, plan)?;
+        }
+        path.pop();
+        state.insert(name.to_string(), Visit::Done);
+        plan.push(name.to_string());
+        Ok(())
+    }
+}
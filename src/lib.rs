@@ -1,21 +1,125 @@
-use crate::public::RunTrait;
+//! st: 开发工具集合, 自动识别项目类型 (cargo、npm、poetry、django 等),
+//! 使用对应的工具执行 编译、格式化、检测、测试 等命令.
+//!
+//! 除了命令行 `st` 之外, 也可以作为库使用:
+//!
+//! ```no_run
+//! use st_cli::{Action, Context, Registry, StConfig, StError, StResult, StTrait};
+//!
+//! /// 自定义插件
+//! struct Make;
+//!
+//! impl StTrait for Make {
+//!     fn name(&self) -> String {
+//!         "make".to_string()
+//!     }
+//!
+//!     fn detect(&self, ctx: &Context) -> bool {
+//!         ctx.has_file("Makefile")
+//!     }
+//!
+//!     fn supports(&self, action: &Action, ctx: &Context) -> bool {
+//!         *action == Action::Test && self.detect(ctx)
+//!     }
+//!
+//!     fn run(&self, _action: &Action, ctx: &Context) -> StResult {
+//!         ctx.run("make".into(), ctx.args.clone())
+//!     }
+//! }
+//!
+//! fn main() -> Result<(), StError> {
+//!     let mut registry = Registry::builtin();
+//!     registry.register(Make);
+//!
+//!     let ctx = Context::new(".");
+//!     println!("detected: {:?}", registry.detect(&ctx));
+//!
+//!     let config = StConfig::load(&ctx.root)?;
+//!     let report = registry.run(&config, &Action::Test, &ctx);
+//!     for outcome in &report.outcomes {
+//!         println!("{}: {}", outcome.plugin, outcome.result.is_ok());
+//!     }
+//!     report.into_result()
+//! }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
 use structopt::StructOpt;
 
+pub(crate) mod cache;
+pub mod config;
+pub(crate) mod output;
+pub(crate) mod parallel;
 pub(crate) mod plugins;
-pub(crate) mod public;
+pub mod public;
+pub mod registry;
 pub(crate) mod run_cmd;
 pub(crate) mod sub_cmd;
 pub(crate) mod utils;
 
+pub use config::StConfig;
+pub use public::{
+    Action, CommandRunner, Context, RecordingRunner, RunTrait, StError, StResult, StTrait,
+};
+pub use registry::{Outcome, Registry, RunReport};
+pub use run_cmd::run_action;
+
 #[derive(Debug, StructOpt)]
-#[structopt(name = "st")]
-pub enum StCli {
+#[structopt(name = "st", setting = structopt::clap::AppSettings::AllowExternalSubcommands)]
+pub struct StCli {
+    /// 在指定的目录中运行, 和 `git -C` 一样
+    ///
+    /// 默认从当前目录开始 向上查找项目的根目录
+    #[structopt(short = "C", global = true, parse(from_os_str))]
+    dir: Option<std::path::PathBuf>,
+    /// 只打印将要执行的命令 (程序、参数、工作目录和环境变量), 不实际执行
+    #[structopt(long, global = true)]
+    dry_run: bool,
+    /// 打印执行的命令, 和 --dry-run 的输出相同
+    #[structopt(long, short = "v", global = true, parse(from_occurrences))]
+    verbose: u8,
+    /// 只运行指定的插件, 可以指定多次 或者用逗号分隔, 例如: --only cargo,npm
+    #[structopt(long, global = true, use_delimiter = true, number_of_values = 1)]
+    only: Vec<String>,
+    /// 不运行指定的插件, 可以指定多次 或者用逗号分隔
+    #[structopt(long, global = true, use_delimiter = true, number_of_values = 1)]
+    skip: Vec<String>,
+    /// 在项目根目录下的所有子项目中运行 (或者配置文件中的 members)
+    #[structopt(long, short = "r", global = true)]
+    recursive: bool,
+    /// 同时执行的插件 (或者子项目) 数量, 大于 1 时 会给每个插件的输出加上前缀
+    #[structopt(long, short = "j", global = true, default_value = "1")]
+    jobs: usize,
+    /// 别名 (例如 `st ci`) 中的某个命令失败之后 继续执行剩下的命令
+    #[structopt(long, short = "k", global = true)]
+    keep_going: bool,
+    /// 不使用缓存, 即使配置中开启了 `cache`
+    #[structopt(long, global = true)]
+    no_cache: bool,
+    /// 把每个插件的命令、用时 以及结果 以 JSON 格式写入这个文件
+    #[structopt(long, global = true, parse(from_os_str))]
+    timings: Option<PathBuf>,
+    /// 输出格式: text 或者 json
+    ///
+    /// json 时 在标准输出中 每行输出一个事件 (命令开始、选中插件、启动子进程、退出码、用时、错误),
+    /// 其他输出 (包括子进程的输出) 都转到标准错误
+    #[structopt(long, global = true, default_value = "text")]
+    output: output::OutputFormat,
+    #[structopt(subcommand)]
+    cmd: StCmd,
+}
+
+/// st 支持的命令
+#[derive(Debug, StructOpt)]
+pub enum StCmd {
     /// 编译
     ///
     /// Rust 项目 默认使用: cargo build
     /// npm 项目 不支持
     /// poetry 项目 默认使用: poetry build 打包
-    Build,
+    Build(public::ExtraArgs),
     /// 清理开发环境
     ///
     /// Rust 使用 cargo clean
@@ -24,7 +128,7 @@ pub enum StCli {
     ///
     /// poetry 不支持
     ///
-    Clean,
+    Clean(public::ExtraArgs),
     /// 格式化代码
     ///
     /// 当前支持
@@ -32,79 +136,470 @@ pub enum StCli {
     /// Python poetry 的项目 [需要安装 black]
     ///
     /// Rust 项目 cargo fmt
-    Format,
+    ///
+    /// `--check` 只检查格式, 用于 CI.
+    /// npm 项目使用 prettier 检查 [需要在 package.json 中依赖 prettier]
+    Format(public::FormatArgs),
     /// 代码检测
     ///
     /// Rust 使用 Cargo clippy
     ///
     /// Python 使用 pylama
-    Lint,
+    ///
+    /// `--changed` / `--staged` 只检测 git 中改动的文件
+    Lint(public::LintArgs),
     /// 检测依赖是否有新版
     ///
     /// Rust 使用 Cargo
     ///
     /// Python 使用 Poetry
-    Outdated,
+    Outdated(public::ExtraArgs),
     /// 运行
     ///
     /// Rust 使用 cargo run
     ///
     /// Python Django 项目使用 django-admin runserver
-    Run,
+    ///
+    /// `st run -- 0.0.0.0:9000` 等价于 `runserver 0.0.0.0:9000`
+    Run(public::ExtraArgs),
     /// 升级依赖版本
     ///
     /// Rust 使用 cargo
     ///
     /// Python 使用 Poetry
-    Update,
+    Update(public::ExtraArgs),
     /// 测试
     ///
     /// Rust 语言使用 cargo test
     ///
     /// Python 使用 pytest
-    Test,
+    ///
+    /// `--` 之后的参数会追加到命令末尾, 例如:
+    /// `st test -- -k foo` 等价于 `pytest <src> -k foo`,
+    /// `st test -- -- --nocapture` 等价于 `cargo test -- --nocapture`
+    Test(public::ExtraArgs),
     /// 同步依赖
-    Sync,
+    Sync(public::ExtraArgs),
     /// 锁定依赖
     ///
     /// 锁定当前的依赖
     ///
     /// Python 使用 Poetry
-    Lock,
+    Lock(public::ExtraArgs),
     /// 本地安装
     ///
     /// 本地安装当前的软件
     /// Rust 使用 cargo install --path .
-    Install,
+    Install(public::ExtraArgs),
     /// 发布
     ///
     /// Python 使用 Poetry 发布到 Pypi
     ///
     /// todo Rust 使用 cargo 发布到 Crates
-    Publish,
+    Publish(public::ExtraArgs),
     /// 提升版本
     Bump(public::bump::Bump),
     /// django 子命令
     Django(sub_cmd::DjangoSubCmd),
+    /// 显示检测到的插件、支持的命令 以及外部工具的版本
+    #[structopt(alias = "info")]
+    Doctor(sub_cmd::Doctor),
+    /// 执行配置文件 `[tasks]` 中的任务, `st x --list` 列出所有任务
+    X(sub_cmd::TaskCmd),
+    /// 安装 git 钩子: pre-commit 检查暂存的文件, pre-push 执行测试
+    Hooks(sub_cmd::HooksCmd),
+    /// 文件改动时 重新执行命令, 例如 `st watch test`
+    Watch(sub_cmd::WatchCmd),
+    /// 配置中的别名 (以及内置的 `ci`), 或者交给支持它的插件 (例如外部插件) 执行
+    #[structopt(external_subcommand)]
+    Custom(Vec<String>),
 }
 
 impl StCli {
+    /// 执行命令
+    ///
+    /// 执行失败时 打印错误信息, 并使用错误对应的退出码退出
     pub fn run(&self) {
+        if self.output == output::OutputFormat::Json {
+            if let Err(e) = output::enable_json() {
+                eprintln!("错误: {}", e);
+                std::process::exit(e.exit_code());
+            }
+        }
+
+        let mut ret = self.try_run();
+        // 命令失败时 也写入执行记录
+        if let Some(path) = self
+            .timings
+            .as_ref()
+            .filter(|_| parallel::current_task().is_none())
+        {
+            ret = ret.and(run_cmd::write_steps(path));
+        }
+        if let Err(e) = ret {
+            eprintln!("错误: {}", e);
+            // 并发执行的子进程 由父进程输出最终的错误
+            if parallel::current_task().is_none() {
+                output::emit(output::Event::Error {
+                    error: output::ErrorInfo::from(&e),
+                });
+            }
+            std::process::exit(e.exit_code());
+        }
+    }
+
+    fn try_run(&self) -> StResult {
+        if let Some(task) = parallel::current_task() {
+            let ret = self.run_task(&task);
+            parallel::save_task_steps()?;
+            return ret;
+        }
+
+        let root = self.project_root()?;
+
+        // 只在根目录监视文件, 子进程再按照 --recursive / --jobs 执行命令
+        if let StCmd::Watch(cmd) = &self.cmd {
+            if !sub_cmd::WatchCmd::is_child() {
+                return cmd.watch(&self.context(&root));
+            }
+        }
+
+        if self.jobs > 1 && self.cmd.action().is_some() && !self.is_alias(&root)? {
+            self.run_parallel(&root)
+        } else if self.recursive {
+            self.run_recursive(&root)
+        } else {
+            self.run_in(&root)
+        }
+    }
+
+    /// 在 `dir` 目录中执行命令的上下文
+    fn context(&self, dir: &Path) -> Context {
+        Context {
+            verbose: self.verbose,
+            dry_run: self.dry_run,
+            ..Context::new(dir)
+        }
+    }
+
+    /// 执行命令, 别名 依次执行其中的每个命令
+    fn run_cmd(&self, config: &StConfig, ctx: &Context) -> StResult {
+        let registry = Registry::discover();
+        match self.cmd.alias(config) {
+            Some((steps, args)) => {
+                if !args.is_empty() {
+                    eprintln!("别名不支持额外的参数, 已忽略: {}", args.join(" "));
+                }
+                run_cmd::run_alias(&registry, config, &steps, ctx, self.keep_going)
+            }
+            None => self.cmd.run(&registry, config, ctx),
+        }
+    }
+
+    /// 命令是否为 `root` 目录配置中的别名
+    ///
+    /// 别名中的命令 依次执行, 不会并发执行
+    fn is_alias(&self, root: &Path) -> Result<bool, StError> {
+        Ok(self.cmd.alias(&self.load_config(root)?).is_some())
+    }
+
+    /// 在 `dir` 目录 加载配置并执行命令
+    fn run_in(&self, dir: &Path) -> StResult {
+        let config = self.load_config(dir)?;
+        self.run_cmd(&config, &self.context(dir))
+    }
+
+    /// 依次在每个子项目中执行命令, 最后打印每个项目的结果
+    fn run_recursive(&self, root: &Path) -> StResult {
+        let projects = self.find_projects(root)?;
+
+        let results: Vec<(String, StResult)> = projects
+            .into_iter()
+            .map(|dir| {
+                println!("==> {}", dir.display());
+                let ret = if dir.is_dir() {
+                    self.run_in(&dir)
+                } else {
+                    Err(StError::MissingProject(dir.display().to_string()))
+                };
+                if let Err(e) = &ret {
+                    eprintln!("{}", e);
+                }
+                (relative_name(root, &dir), ret)
+            })
+            .collect();
+
+        run_cmd::print_summary("project", results)
+    }
+
+    /// 使用子进程 并发执行每个 (项目, 插件)
+    fn run_parallel(&self, root: &Path) -> StResult {
+        let (action, _) = self.cmd.action().expect("不支持并发执行的命令");
+        let projects = if self.recursive {
+            self.find_projects(root)?
+        } else {
+            vec![root.to_path_buf()]
+        };
+
+        // 钩子由父进程执行, 每个项目只执行一次
+        let start = Instant::now();
+        let registry = Registry::discover();
+        let mut tasks = vec![];
+        let mut hook_results = vec![];
+        let mut hooked = vec![];
+        for dir in projects {
+            let config = self.load_config(&dir)?;
+            let ctx = self.context(&dir);
+            let prefix = if self.recursive {
+                format!("{}/", relative_name(root, &dir))
+            } else {
+                String::new()
+            };
+
+            output::emit(output::Event::ActionStart {
+                project: dir.display().to_string(),
+                action: action.name().to_string(),
+            });
+            if let Err(e) = run_cmd::run_pre_hook(&config, &action, &ctx) {
+                let ret = Err(e);
+                output::emit(output::Event::action_finished(
+                    &dir,
+                    action.name(),
+                    &ret,
+                    start.elapsed(),
+                ));
+                hook_results.push((format!("{}pre_{}", prefix, action.name()), ret));
+                continue;
+            }
+            for plugin in run_cmd::supported_plugins(&registry, &config, &action, &ctx) {
+                tasks.push(parallel::Task {
+                    label: format!("{}{}", prefix, plugin),
+                    dir: dir.clone(),
+                    plugin,
+                });
+            }
+            hooked.push((dir, config, prefix));
+        }
+        if tasks.is_empty() && hook_results.is_empty() {
+            for (dir, _, _) in &hooked {
+                output::emit(output::Event::action_finished(
+                    dir,
+                    action.name(),
+                    &Err(StError::NoHandler),
+                    start.elapsed(),
+                ));
+            }
+            let config = self.load_config(root)?;
+            return Err(self.cmd.unknown_command(&config, StError::NoHandler));
+        }
+
+        let task_results = parallel::run_tasks(tasks, self.jobs)?;
+        for (dir, config, prefix) in hooked {
+            let mut rets = task_results.iter().filter(|(t, _)| t.dir == dir).peekable();
+            if rets.peek().is_none() {
+                output::emit(output::Event::action_finished(
+                    &dir,
+                    action.name(),
+                    &Err(StError::NoHandler),
+                    start.elapsed(),
+                ));
+                continue;
+            }
+            let failed = rets.find_map(|(_, ret)| ret.as_ref().err());
+            let hook_ret =
+                run_cmd::run_post_hook(&config, &action, &self.context(&dir), failed.is_none());
+            // 和 run_action 相同: 命令失败时 使用命令的错误, 否则使用后置钩子的结果
+            let error = failed.or(hook_ret.as_ref().err());
+            output::emit(output::Event::ActionFinished {
+                project: dir.display().to_string(),
+                action: action.name().to_string(),
+                status: if error.is_some() { "fail" } else { "pass" },
+                duration_ms: start.elapsed().as_millis() as u64,
+                error: error.map(output::ErrorInfo::from),
+            });
+            if let Err(e) = hook_ret {
+                hook_results.push((format!("{}post_{}", prefix, action.name()), Err(e)));
+            }
+        }
+
+        let results = task_results
+            .into_iter()
+            .map(|(task, ret)| (task.label, ret))
+            .chain(hook_results)
+            .collect();
+        run_cmd::print_summary("task", results)
+    }
+
+    /// 作为并发执行的子进程 执行单个任务
+    fn run_task(&self, task: &parallel::Task) -> StResult {
+        let mut config = self.load_config(&task.dir)?;
+        config.only = vec![task.plugin.clone()];
+        // 钩子已经由父进程执行
+        config.hooks.clear();
+        self.run_cmd(&config, &self.context(&task.dir))
+    }
+
+    /// 查找 `root` 下的子项目, 优先使用配置中的 `members`
+    fn find_projects(&self, root: &Path) -> Result<Vec<PathBuf>, StError> {
+        let config = self.load_config(root)?;
+        let projects: Vec<PathBuf> = if config.members.is_empty() {
+            utils::find_sub_projects(root)
+        } else {
+            config.members.iter().map(|m| root.join(m)).collect()
+        };
+        if projects.is_empty() {
+            return Err(StError::MissingProject(root.display().to_string()));
+        }
+        Ok(projects)
+    }
+
+    /// 加载 `dir` 目录的配置, 并使用命令行参数覆盖
+    fn load_config(&self, dir: &Path) -> Result<StConfig, StError> {
+        let mut config = StConfig::load(dir)?;
+
+        if !self.only.is_empty() {
+            config.only = self.only.clone();
+        }
+        config.skip.extend(self.skip.iter().cloned());
+        if self.no_cache {
+            config.cache = false;
+        }
+        Ok(config)
+    }
+
+    /// 项目的根目录
+    ///
+    /// 从 `-C` 指定的目录 (默认为当前目录) 开始向上查找, 找不到时 使用开始的目录
+    fn project_root(&self) -> Result<PathBuf, StError> {
+        let mut start = utils::current_dir()?;
+        if let Some(dir) = &self.dir {
+            start = start
+                .join(dir)
+                .canonicalize()
+                .map_err(|e| StError::io(format!("切换到目录 {} 失败", dir.display()), e))?;
+        }
+
+        match utils::find_project_root(&start) {
+            Some(root) if root != start => {
+                println!("项目目录: {}", root.display());
+                Ok(root)
+            }
+            _ => Ok(start),
+        }
+    }
+}
+
+/// `dir` 相对于 `root` 的名称
+fn relative_name(root: &Path, dir: &Path) -> String {
+    let name = dir.strip_prefix(root).unwrap_or(dir).display().to_string();
+    if name.is_empty() {
+        ".".to_string()
+    } else {
+        name
+    }
+}
+
+impl StCmd {
+    /// 通过插件执行的命令 以及透传的参数
+    ///
+    /// 子命令 (例如 `django`) 返回 `None`
+    fn action(&self) -> Option<(Action, &[String])> {
+        Some(match self {
+            StCmd::Build(extra) => (Action::Build, &extra.args),
+            StCmd::Clean(extra) => (Action::Clean, &extra.args),
+            StCmd::Format(format) => (
+                Action::Format {
+                    check: format.check,
+                },
+                &format.extra.args,
+            ),
+            StCmd::Lint(lint) => (Action::Lint, &lint.extra.args),
+            StCmd::Outdated(extra) => (Action::Outdated, &extra.args),
+            StCmd::Run(extra) => (Action::Run, &extra.args),
+            StCmd::Update(extra) => (Action::Update, &extra.args),
+            StCmd::Test(extra) => (Action::Test, &extra.args),
+            StCmd::Sync(extra) => (Action::Sync, &extra.args),
+            StCmd::Lock(extra) => (Action::Lock, &extra.args),
+            StCmd::Install(extra) => (Action::Install, &extra.args),
+            StCmd::Publish(extra) => (Action::Publish, &extra.args),
+            StCmd::Bump(bump) => (Action::Bump(*bump), &[]),
+            StCmd::Custom(cmd) => (Action::from_name(&cmd[0]), &cmd[1..]),
+            // 监视文件的子进程 执行 `st watch` 之后的命令
+            StCmd::Watch(cmd) => {
+                let (action, args) = run_cmd::parse_step(cmd.child_step()?);
+                (
+                    action,
+                    args.strip_prefix(&["--".to_string()]).unwrap_or(args),
+                )
+            }
+            StCmd::Django(_) | StCmd::Doctor(_) | StCmd::X(_) | StCmd::Hooks(_) => return None,
+        })
+    }
+
+    /// 别名对应的命令 以及用户传入的参数
+    fn alias(&self, config: &StConfig) -> Option<(Vec<Vec<String>>, &[String])> {
+        match self {
+            StCmd::Custom(cmd) => config.alias(&cmd[0]).map(|steps| (steps, &cmd[1..])),
+            StCmd::Watch(cmd) => {
+                let step = cmd.child_step()?;
+                config.alias(&step[0]).map(|steps| (steps, &step[1..]))
+            }
+            _ => None,
+        }
+    }
+
+    /// 只处理 git 中改动的文件的参数
+    fn changed(&self) -> Option<&public::ChangedArgs> {
+        match self {
+            StCmd::Format(format) => Some(&format.changed),
+            StCmd::Lint(lint) => Some(&lint.changed),
+            _ => None,
+        }
+    }
+
+    /// 没有插件支持的自定义命令 视为输错了命令, 列出可用的命令
+    fn unknown_command(&self, config: &StConfig, e: StError) -> StError {
+        match (self, e) {
+            (StCmd::Custom(cmd), StError::NoHandler) => {
+                // clap 2 的子命令列表 只能通过 `App::p` 获取
+                let mut commands: Vec<String> = StCli::clap()
+                    .p
+                    .subcommands
+                    .iter()
+                    .map(|s| s.p.meta.name.clone())
+                    .collect();
+                commands.extend(config.alias_names());
+                StError::UnknownCommand {
+                    name: cmd[0].clone(),
+                    commands,
+                }
+            }
+            (_, e) => e,
+        }
+    }
+
+    fn run(&self, registry: &Registry, config: &StConfig, ctx: &Context) -> StResult {
+        if let Some((action, args)) = self.action() {
+            let mut ctx = ctx.clone().with_args(args.to_vec());
+            match self.changed() {
+                Some(c) if c.staged => ctx.files = Some(utils::git::staged_files(&ctx)?),
+                Some(c) if c.changed => {
+                    ctx.files = Some(utils::git::changed_files(
+                        &ctx,
+                        c.base.as_deref().unwrap_or("HEAD"),
+                    )?)
+                }
+                _ => {}
+            }
+            return run_cmd::run_action(registry, config, &action, &ctx)
+                .map_err(|e| self.unknown_command(config, e));
+        }
         match self {
-            StCli::Build => run_cmd::run_build_cmd(),
-            StCli::Clean => run_cmd::run_clean_cmd(),
-            StCli::Format => run_cmd::run_format_cmd(),
-            StCli::Lint => run_cmd::run_lint_cmd(),
-            StCli::Outdated => run_cmd::run_outdated_cmd(),
-            StCli::Run => run_cmd::run_run_cmd(),
-            StCli::Update => run_cmd::run_update_cmd(),
-            StCli::Test => run_cmd::run_test_cmd(),
-            StCli::Sync => run_cmd::run_sync_cmd(),
-            StCli::Lock => run_cmd::run_lock_cmd(),
-            StCli::Install => run_cmd::run_install_cmd(),
-            StCli::Publish => run_cmd::run_publish_cmd(),
-            StCli::Bump(bump) => run_cmd::run_bump_cmd(bump),
-            StCli::Django(cmd) => cmd.run(),
+            StCmd::Django(cmd) => cmd.run(ctx),
+            StCmd::Doctor(cmd) => cmd.run(ctx),
+            StCmd::X(cmd) => cmd.run(ctx),
+            StCmd::Hooks(cmd) => cmd.run(ctx),
+            _ => unreachable!("已经通过插件执行"),
         }
     }
 }
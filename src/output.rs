@@ -0,0 +1,197 @@
+//! `--output json` 时 在标准输出中输出的事件
+//!
+//! 每行一个 JSON 对象, `event` 字段为事件类型.
+//! 其他的输出 (包括子进程的输出) 都会转到标准错误, 不会混入事件中
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::public::{StError, StResult};
+
+/// 输出事件的文件 (原来的标准输出), 没有开启 `--output json` 时 为空
+static EVENTS: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// 输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// 给人看的文本
+    Text,
+    /// JSON 事件流
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("不支持的输出格式: {}, 可选: text、json", s)),
+        }
+    }
+}
+
+/// 事件中的错误
+#[derive(Debug, Serialize)]
+pub struct ErrorInfo {
+    /// 见 `StError::kind`
+    pub kind: &'static str,
+    pub message: String,
+    pub exit_code: i32,
+}
+
+impl From<&StError> for ErrorInfo {
+    fn from(e: &StError) -> Self {
+        ErrorInfo {
+            kind: e.kind(),
+            message: e.to_string(),
+            exit_code: e.exit_code(),
+        }
+    }
+}
+
+/// 输出的事件
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// 开始执行命令 (包括钩子)
+    ActionStart { project: String, action: String },
+    /// 选中了执行命令的插件
+    PluginSelected {
+        project: String,
+        action: String,
+        plugin: String,
+    },
+    /// 启动子进程
+    CommandSpawned { command: String, cwd: String },
+    /// 子进程退出, 被信号终止时 没有退出码
+    CommandExited {
+        command: String,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+    },
+    /// 插件执行完成
+    PluginFinished {
+        project: String,
+        action: String,
+        plugin: String,
+        /// `pass`、`fail`、`cached` 或者 `dry-run`
+        status: &'static str,
+        duration_ms: u64,
+        error: Option<ErrorInfo>,
+    },
+    /// 命令执行完成
+    ActionFinished {
+        project: String,
+        action: String,
+        status: &'static str,
+        duration_ms: u64,
+        error: Option<ErrorInfo>,
+    },
+    /// st 以错误退出
+    Error { error: ErrorInfo },
+}
+
+impl Event {
+    pub fn action_finished(
+        project: &Path,
+        action: &str,
+        ret: &StResult,
+        duration: Duration,
+    ) -> Self {
+        Event::ActionFinished {
+            project: project.display().to_string(),
+            action: action.to_string(),
+            status: status(ret),
+            duration_ms: duration.as_millis() as u64,
+            error: ret.as_ref().err().map(ErrorInfo::from),
+        }
+    }
+}
+
+/// 结果对应的状态
+pub fn status(ret: &StResult) -> &'static str {
+    match ret {
+        Ok(()) => "pass",
+        Err(_) => "fail",
+    }
+}
+
+/// 开启 JSON 事件流
+///
+/// 标准输出只用来输出事件, 之后写入标准输出的内容 (包括子进程继承的标准输出) 都会转到标准错误
+#[cfg(unix)]
+pub fn enable_json() -> StResult {
+    use std::os::unix::io::FromRawFd;
+
+    let _ = io::stdout().flush();
+    let fd = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if fd < 0 {
+        return Err(StError::io("复制标准输出失败", io::Error::last_os_error()));
+    }
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(StError::io(
+            "重定向标准输出失败",
+            io::Error::last_os_error(),
+        ));
+    }
+    let _ = EVENTS.set(Mutex::new(unsafe { File::from_raw_fd(fd) }));
+    Ok(())
+}
+
+/// 开启 JSON 事件流
+///
+/// 需要重定向标准输出, 目前只支持 unix
+#[cfg(not(unix))]
+pub fn enable_json() -> StResult {
+    Err(StError::io(
+        "开启 --output json 失败",
+        io::Error::new(io::ErrorKind::Unsupported, "只支持 unix"),
+    ))
+}
+
+/// 是否开启了 JSON 事件流
+pub fn is_json() -> bool {
+    EVENTS.get().is_some()
+}
+
+/// 输出事件, 没有开启 JSON 事件流时 忽略
+pub fn emit(event: Event) {
+    if let Some(events) = EVENTS.get() {
+        match serde_json::to_string(&event) {
+            Ok(line) => emit_line(events, &line),
+            Err(e) => eprintln!("序列化事件失败: {}", e),
+        }
+    }
+}
+
+/// 原样输出一行事件 (例如子进程输出的事件), 没有开启 JSON 事件流时 忽略
+pub fn emit_raw(line: &str) {
+    if let Some(events) = EVENTS.get() {
+        emit_line(events, line);
+    }
+}
+
+fn emit_line(events: &Mutex<File>, line: &str) {
+    let mut f = events.lock().expect("获取事件输出失败");
+    let _ = writeln!(f, "{}", line).and_then(|_| f.flush());
+}
+
+/// 子进程的标准输出: 开启 JSON 事件流时 为事件流, 否则继承当前进程的
+///
+/// 用于同样输出事件的 st 子进程
+pub fn child_stdout() -> Stdio {
+    EVENTS
+        .get()
+        .and_then(|events| events.lock().ok()?.try_clone().ok())
+        .map(Stdio::from)
+        .unwrap_or_else(Stdio::inherit)
+}
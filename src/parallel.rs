@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::output;
+use crate::public::{StError, StResult};
+use crate::run_cmd;
+
+/// 子进程执行任务时 所在的目录
+const TASK_DIR_ENV: &str = "ST_TASK_DIR";
+/// 子进程执行任务时 使用的插件
+const TASK_PLUGIN_ENV: &str = "ST_TASK_PLUGIN";
+/// 子进程把执行记录 写入这个文件, 由父进程合并
+const TASK_STEPS_ENV: &str = "ST_TASK_STEPS";
+
+/// 只给 st 子进程使用的环境变量, 执行工具时 需要删除,
+/// 否则工具中再执行的 st (例如 npm 脚本中的 `st test`) 会当作子进程 执行父进程的任务
+pub(crate) const TASK_ENVS: [&str; 3] = [TASK_DIR_ENV, TASK_PLUGIN_ENV, TASK_STEPS_ENV];
+
+/// 并发执行的任务: 在 `dir` 目录中 只使用 `plugin` 插件执行当前命令
+#[derive(Debug, Clone)]
+pub struct Task {
+    /// 输出的前缀
+    pub label: String,
+    pub dir: PathBuf,
+    pub plugin: String,
+}
+
+/// 当前进程 是否为并发执行时启动的子进程
+///
+/// 子进程使用和父进程相同的命令行参数,
+/// 通过环境变量指定 目录 和 插件
+pub fn current_task() -> Option<Task> {
+    let dir = env::var_os(TASK_DIR_ENV)?;
+    let plugin = env::var(TASK_PLUGIN_ENV).ok()?;
+    Some(Task {
+        label: plugin.clone(),
+        dir: PathBuf::from(dir),
+        plugin,
+    })
+}
+
+/// 子进程把执行记录 写入父进程指定的文件
+pub fn save_task_steps() -> StResult {
+    match env::var_os(TASK_STEPS_ENV) {
+        Some(path) => run_cmd::write_steps(Path::new(&path)),
+        None => Ok(()),
+    }
+}
+
+/// 合并子进程的执行记录, 读取之后 删除文件
+fn load_task_steps(path: &Path) {
+    let steps = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    let _ = fs::remove_file(path);
+    match serde_json::from_str::<Vec<run_cmd::Step>>(&steps) {
+        Ok(steps) => run_cmd::record_steps(steps),
+        Err(e) => eprintln!("读取执行记录 {} 失败: {}", path.display(), e),
+    }
+}
+
+/// 最多使用 `jobs` 个子进程 并发执行所有任务
+///
+/// 子进程的输出 会加上 `[label]` 前缀, 返回结果的顺序和 `tasks` 相同
+pub fn run_tasks(tasks: Vec<Task>, jobs: usize) -> Result<Vec<(Task, StResult)>, StError> {
+    let exe = env::current_exe().map_err(|e| StError::io("获取 st 的路径失败", e))?;
+    let queue = Mutex::new(tasks.iter().enumerate().collect::<VecDeque<_>>());
+    let results: Mutex<Vec<Option<StResult>>> =
+        Mutex::new((0..tasks.len()).map(|_| None).collect());
+
+    thread::scope(|s| {
+        for _ in 0..jobs.max(1) {
+            s.spawn(|| loop {
+                let next = queue.lock().expect("获取任务失败").pop_front();
+                let (idx, task) = match next {
+                    Some(v) => v,
+                    None => break,
+                };
+                let ret = run_task(&exe, idx, task);
+                results.lock().expect("保存结果失败")[idx] = Some(ret);
+            });
+        }
+    });
+
+    Ok(tasks
+        .into_iter()
+        .zip(results.into_inner().expect("获取结果失败"))
+        .map(|(task, ret)| (task, ret.expect("任务没有执行")))
+        .collect())
+}
+
+/// 启动子进程 执行单个任务
+fn run_task(exe: &PathBuf, idx: usize, task: &Task) -> StResult {
+    let steps = env::temp_dir().join(format!("st-steps-{}-{}.json", process::id(), idx));
+    let mut child = match Command::new(exe)
+        .args(env::args_os().skip(1))
+        .env(TASK_DIR_ENV, &task.dir)
+        .env(TASK_PLUGIN_ENV, &task.plugin)
+        .env(TASK_STEPS_ENV, &steps)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(e) => return Err(StError::io(format!("[{}] 启动子进程失败", task.label), e)),
+    };
+
+    let stdout = child.stdout.take().expect("获取子进程输出失败");
+    let stderr = child.stderr.take().expect("获取子进程输出失败");
+    thread::scope(|s| {
+        s.spawn(|| forward_lines(stdout, &task.label, false));
+        s.spawn(|| forward_lines(stderr, &task.label, true));
+    });
+
+    let status = child.wait();
+    load_task_steps(&steps);
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(StError::ChildFailed {
+            command: format!("st ({})", task.label),
+            code: status.code(),
+        }),
+        Err(e) => Err(StError::io(format!("[{}] 等待子进程失败", task.label), e)),
+    }
+}
+
+/// 逐行转发子进程的输出
+fn forward_lines<R: Read>(r: R, label: &str, is_stderr: bool) {
+    for line in BufReader::new(r).split(b'\n') {
+        let line = match line {
+            Ok(l) => l,
+            Err(_) => break,
+        };
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches('\r');
+        if output::is_json() && !is_stderr {
+            // 子进程的标准输出 只有事件
+            output::emit_raw(line);
+        } else if is_stderr {
+            eprintln!("[{}] {}", label, line);
+        } else {
+            println!("[{}] {}", label, line);
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::public::{Action, Context, StError, StResult, StTrait};
+use crate::utils;
+
+/// 外部插件可执行文件的前缀
+const PREFIX: &str = "st-";
+
+/// 确认是否为 st 插件的参数
+const HANDSHAKE_ARG: &str = "--st-plugin";
+/// 等待 `--st-plugin` 的时间, 其他程序 (例如 stlink 的 `st-util`) 可能不会退出
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+/// 等待 `detect`、`supports` 的时间
+const QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 保存握手结果的文件 (相对于用户的缓存目录)
+const HANDSHAKE_CACHE: &str = "st/external-plugins.json";
+
+/// 本次执行中 找到的外部插件, 只查找一次
+static DISCOVERED: OnceLock<Vec<External>> = OnceLock::new();
+
+/// 外部插件
+///
+/// PATH 中名为 `st-<name>` 的可执行文件, 和 cargo、git 的扩展方式一致.
+/// st 通过以下命令和它交互:
+///
+/// * `st-<name> --st-plugin`: 确认是 st 插件, 标准输出为 `{"st_plugin": true}`.
+///   其他同名前缀的程序 (例如 stlink 的 `st-flash`、`st-util`) 不会被加载
+/// * `st-<name> detect`: 当前目录是否为它能处理的项目,
+///   标准输出为 `{"detected": true}`
+/// * `st-<name> supports <action>`: 当前目录是否支持 `action` 命令,
+///   标准输出为 `{"supported": true}`
+/// * `st-<name> run <action> [args...]`: 执行命令,
+///   直接继承 st 的标准输入、输出, 退出码即为执行结果.
+///   `bump` 之后为环境名称, `format --check` 之后为 `--check`
+///
+/// `--st-plugin` 的结果保存在用户的缓存目录中, 可执行文件没有改动时 不会再次执行.
+/// `detect`、`supports` 执行失败、超时 或者输出无法解析时, 视为 `false`
+#[derive(Debug, Clone)]
+pub struct External {
+    name: String,
+    path: PathBuf,
+}
+
+impl External {
+    /// 查找 PATH 中的所有外部插件, 结果在本次执行中 会被复用
+    ///
+    /// 同名的插件 只使用 PATH 中最靠前的, 和内置插件同名的插件会被忽略
+    pub fn discover(builtin: &[String]) -> Vec<External> {
+        DISCOVERED
+            .get_or_init(|| {
+                let candidates = Self::candidates(builtin);
+                let mut cache = HandshakeCache::load();
+                let stamps: Vec<Option<Stamp>> =
+                    candidates.iter().map(|p| Stamp::of(&p.path)).collect();
+                let pending: Vec<&External> = candidates
+                    .iter()
+                    .zip(&stamps)
+                    .filter(|(p, stamp)| cache.get(&p.path, stamp.as_ref()).is_none())
+                    .map(|(p, _)| p)
+                    .collect();
+
+                // 同时确认所有没有缓存的候选, 最多等待一个超时时间
+                let confirmed: Vec<bool> = thread::scope(|s| {
+                    let handles: Vec<_> = pending
+                        .iter()
+                        .map(|p| s.spawn(move || p.handshake()))
+                        .collect();
+                    handles
+                        .into_iter()
+                        .map(|h| h.join().unwrap_or(false))
+                        .collect()
+                });
+                if !pending.is_empty() {
+                    for (p, plugin) in pending.iter().zip(confirmed) {
+                        if let Some(stamp) = Stamp::of(&p.path) {
+                            cache.entries.insert(p.path.clone(), (stamp, plugin));
+                        }
+                    }
+                    cache.save();
+                }
+
+                candidates
+                    .into_iter()
+                    .zip(stamps)
+                    .filter(|(p, stamp)| cache.get(&p.path, stamp.as_ref()) == Some(true))
+                    .map(|(p, _)| p)
+                    .collect()
+            })
+            .clone()
+    }
+
+    /// PATH 中所有名为 `st-<name>` 的可执行文件
+    fn candidates(builtin: &[String]) -> Vec<External> {
+        let paths = match env::var_os("PATH") {
+            Some(p) => p,
+            None => return vec![],
+        };
+
+        let mut seen: HashSet<String> = builtin.iter().cloned().collect();
+        let mut plugins = vec![];
+        for dir in env::split_paths(&paths) {
+            let mut entries: Vec<PathBuf> = match dir.read_dir() {
+                Ok(d) => d.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+                Err(_) => continue,
+            };
+            entries.sort();
+
+            for path in entries {
+                let name = match Self::plugin_name(&path) {
+                    Some(n) => n,
+                    None => continue,
+                };
+                if seen.insert(name.clone()) {
+                    plugins.push(External { name, path });
+                }
+            }
+        }
+        plugins
+    }
+
+    /// 从可执行文件的路径中 解析插件名称
+    fn plugin_name(path: &Path) -> Option<String> {
+        if !is_executable(path) {
+            return None;
+        }
+        let file_name = path.file_stem()?.to_str()?;
+        let name = file_name.strip_prefix(PREFIX)?;
+        if name.is_empty() {
+            return None;
+        }
+        Some(name.to_string())
+    }
+
+    /// 执行 `st-<name> --st-plugin`, 确认是 st 插件
+    ///
+    /// 不是插件的程序 可能会等待输入 或者输出错误信息, 所以标准输入、标准错误都为空
+    fn handshake(&self) -> bool {
+        let mut cmd = Command::new(&self.path);
+        cmd.arg(HANDSHAKE_ARG)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null());
+        match utils::output_with_timeout(&mut cmd, HANDSHAKE_TIMEOUT) {
+            Ok((status, stdout)) if status.success() => {
+                serde_json::from_slice::<serde_json::Value>(&stdout)
+                    .ok()
+                    .and_then(|v| v.get("st_plugin")?.as_bool())
+                    .unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+
+    /// 执行 `detect`、`supports` 并读取输出中 `key` 的值
+    fn query(&self, ctx: &Context, args: &[&str], key: &str) -> bool {
+        // 无论是否为 dry-run 都需要执行, 以保证选择相同的插件
+        let o = match ctx.output_timeout(self.path.clone(), utils::to_args(args), QUERY_TIMEOUT) {
+            Ok(o) => o,
+            Err(StError::ChildFailed { .. }) => return false,
+            Err(e) => {
+                eprintln!("执行插件 {} 失败: {}", self.name, e);
+                return false;
+            }
+        };
+
+        match serde_json::from_str::<serde_json::Value>(&o) {
+            Ok(v) => v.get(key).and_then(|v| v.as_bool()).unwrap_or(false),
+            Err(e) => {
+                eprintln!("解析插件 {} 的输出失败: {}", self.name, e);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|e| e == "exe")
+}
+
+/// 可执行文件的修改时间 以及大小, 改动后 需要重新握手
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    modified_secs: u64,
+    modified_nanos: u32,
+    len: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Stamp> {
+        let meta = path.metadata().ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Stamp {
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            len: meta.len(),
+        })
+    }
+}
+
+/// 保存的握手结果: 可执行文件 -> (文件状态, 是否为 st 插件)
+///
+/// 避免每次执行 st (包括 `--jobs` 的每个子进程) 都等待不是插件的程序超时
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HandshakeCache {
+    entries: BTreeMap<PathBuf, (Stamp, bool)>,
+}
+
+impl HandshakeCache {
+    /// 用户的缓存目录中 保存结果的文件, 找不到缓存目录时 不保存
+    fn file() -> Option<PathBuf> {
+        let dir = env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+        Some(dir.join(HANDSHAKE_CACHE))
+    }
+
+    fn load() -> Self {
+        Self::file()
+            .and_then(|f| fs::read_to_string(f).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// 先写入临时文件 再重命名, 避免同时执行的 st 读到一半的内容. 保存失败时 忽略, 下次重新握手
+    fn save(&self) {
+        let file = match Self::file() {
+            Some(f) => f,
+            None => return,
+        };
+        if let (Some(dir), Ok(s)) = (file.parent(), serde_json::to_string(self)) {
+            let tmp = file.with_extension(format!("{}.tmp", std::process::id()));
+            let _ = fs::create_dir_all(dir)
+                .and_then(|_| fs::write(&tmp, s))
+                .and_then(|_| fs::rename(&tmp, &file));
+        }
+    }
+
+    /// 文件状态相同时 保存的结果
+    fn get(&self, path: &Path, stamp: Option<&Stamp>) -> Option<bool> {
+        match self.entries.get(path) {
+            Some((s, plugin)) if Some(s) == stamp => Some(*plugin),
+            _ => None,
+        }
+    }
+}
+
+impl StTrait for External {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn detect(&self, ctx: &Context) -> bool {
+        self.query(ctx, &["detect"], "detected")
+    }
+
+    fn supports(&self, action: &Action, ctx: &Context) -> bool {
+        self.query(ctx, &["supports", action.name()], "supported")
+    }
+
+    fn run(&self, action: &Action, ctx: &Context) -> StResult {
+        let mut cmd = vec!["run".to_string(), action.name().to_string()];
+        match action {
+            Action::Bump(bump) => cmd.push(bump.name().to_string()),
+            Action::Format { check: true } => cmd.push("--check".to_string()),
+            _ => {}
+        }
+        ctx.run(self.path.clone(), utils::append_args(cmd, &ctx.args))
+    }
+}
@@ -1,20 +1,46 @@
-use crate::public::StTrait;
+use crate::public::{Action, Context, StError, StResult, StTrait};
 use crate::utils;
 
+/// prettier 支持的文件扩展名
+const PRETTIER_EXTS: &[&str] = &[
+    "js", "jsx", "mjs", "cjs", "ts", "tsx", "json", "css", "scss", "less", "md", "html", "vue",
+    "yaml", "yml",
+];
+
 #[derive(Default)]
 pub struct Npm {}
 
 impl Npm {
     #[inline]
-    fn check_npm_project(&self) -> bool {
+    fn check_npm_project(&self, ctx: &Context) -> bool {
         // package.json 文件是 npm 项目的标准配置文件
-        utils::check_current_dir_file_exists("package.json")
+        ctx.has_file("package.json")
+    }
+
+    /// package.json 的 `dependencies` 或者 `devDependencies` 中 是否有 `name`
+    fn has_dependency(&self, ctx: &Context, name: &str) -> bool {
+        let f = "package.json";
+        let v = match std::fs::read_to_string(ctx.path(f))
+            .map_err(|e| StError::io(format!("读取 {} 失败", f), e))
+            .and_then(|s| {
+                serde_json::from_str::<serde_json::Value>(&s)
+                    .map_err(|e| StError::bad_manifest(f, e))
+            }) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
+        };
+        ["dependencies", "devDependencies"]
+            .iter()
+            .any(|key| v.get(key).and_then(|d| d.get(name)).is_some())
     }
 
     #[inline]
-    fn npm_run(&self, args: Vec<String>) {
-        let npm = utils::get_exec_path("npm");
-        utils::run_with_args(npm, args);
+    fn npm_run(&self, ctx: &Context, args: Vec<String>) -> StResult {
+        let npm = ctx.exec_path("npm")?;
+        ctx.run(npm, args)
     }
 }
 
@@ -23,36 +49,57 @@ impl StTrait for Npm {
         String::from("npm")
     }
 
-    /// 支持 `clean`
-    /// 删除 npm 缓存的 文件
-    fn support_clean(&self) -> bool {
-        self.check_npm_project()
-    }
-
-    fn do_clean(&self) {
-        self.npm_run(vec!["cache".to_string(), "clean".to_string()])
+    fn detect(&self, ctx: &Context) -> bool {
+        self.check_npm_project(ctx)
     }
 
-    fn support_outdated(&self) -> bool {
-        self.check_npm_project()
+    fn supports(&self, action: &Action, ctx: &Context) -> bool {
+        match action {
+            Action::Clean | Action::Outdated | Action::Update => self.check_npm_project(ctx),
+            // 只检查格式, 不使用 prettier 格式化
+            Action::Format { check: true } => {
+                self.check_npm_project(ctx) && self.has_dependency(ctx, "prettier")
+            }
+            _ => false,
+        }
     }
 
-    /// 检查依赖是否已经过时
-    fn do_outdated(&self) {
-        self.npm_run(vec!["outdated".to_string()])
+    fn handles_files(&self, action: &Action) -> bool {
+        matches!(action, Action::Format { check: true })
     }
 
-    fn support_run(&self) -> bool {
-        false
-    }
-
-    fn do_run(&self) {}
-
-    fn support_update(&self) -> bool {
-        self.check_npm_project()
-    }
+    fn run(&self, action: &Action, ctx: &Context) -> StResult {
+        if let Action::Format { check: true } = action {
+            // 限制了文件时 只检查 prettier 支持的文件
+            let targets = match ctx.files_with_ext(PRETTIER_EXTS) {
+                Some(files) if files.is_empty() => {
+                    println!("没有改动的 prettier 支持的文件, 跳过 {}", action.name());
+                    return Ok(());
+                }
+                Some(files) => files,
+                None => vec![".".to_string()],
+            };
+            // 使用项目中安装的 prettier, 会列出格式不正确的文件
+            let args = utils::append_args(
+                utils::to_args(&["exec", "--", "prettier", "--check"]),
+                &ctx.args,
+            );
+            return self.npm_run(ctx, utils::append_args(args, &targets));
+        }
 
-    fn do_update(&self) {
-        self.npm_run(vec!["update".to_string()])
+        let cmd: &[&str] = match action {
+            // 删除 npm 缓存的 文件
+            Action::Clean => &["cache", "clean"],
+            // 检查依赖是否已经过时
+            Action::Outdated => &["outdated"],
+            Action::Update => &["update"],
+            _ => {
+                return Err(StError::Unsupported {
+                    plugin: self.name(),
+                    action: action.name().to_string(),
+                })
+            }
+        };
+        self.npm_run(ctx, utils::append_args(utils::to_args(cmd), &ctx.args))
     }
 }
@@ -1,8 +1,10 @@
+mod external;
 mod js_npm;
 mod python_django;
 mod python_poetry;
 mod rust_cargo;
 
+pub use external::External;
 pub use js_npm::Npm;
 pub use python_django::Django;
 pub use python_poetry::Poetry;
@@ -1,8 +1,6 @@
-use std::env;
-
 use super::Poetry;
 use crate::public::bump::{Bump, VerNewOld, Version};
-use crate::public::StTrait;
+use crate::public::{Action, Context, StError, StResult, StTrait};
 use crate::utils;
 
 /// Python Django Build Runner
@@ -10,32 +8,33 @@ use crate::utils;
 pub struct Django {}
 
 impl Django {
-    fn set_django_env(prod: bool) {
-        let src = Poetry::ensure_get_src_dir();
-        utils::set_env("DJANGO_SETTINGS_MODULE", format!("{}.settings", src));
-        if prod {
-            utils::set_env("DJANGO_PROD", "1");
+    /// 设置了 django 必要环境变量的上下文
+    fn django_context(ctx: &Context, prod: bool) -> Result<Context, StError> {
+        let src = Poetry::get_src_dir(ctx)?;
+        let ctx = ctx
+            .clone()
+            .with_env("DJANGO_SETTINGS_MODULE", format!("{}.settings", src));
+        Ok(if prod {
+            ctx.with_env("DJANGO_PROD", "1")
         } else {
-            utils::set_env("DJANGO_DEV", "1");
-        }
+            ctx.with_env("DJANGO_DEV", "1")
+        })
     }
 
-    pub fn poetry_django_admin_prod_run(args: Vec<String>) {
-        Self::set_django_env(true); // 设置必要的环境变量
-        Self::do_poetry_django_admin_run(args);
+    pub fn poetry_django_admin_prod_run(ctx: &Context, args: Vec<String>) -> StResult {
+        let ctx = Self::django_context(ctx, true)?; // 设置必要的环境变量
+        Self::do_poetry_django_admin_run(&ctx, args)
     }
 
     /// 实际执行的命令为:
     ///
     /// poetry run python manage.py ...args
-    pub fn poetry_django_admin_dev_run(args: Vec<String>) {
-        Self::set_django_env(false); // 设置必要的环境变量
-        Self::do_poetry_django_admin_run(args);
+    pub fn poetry_django_admin_dev_run(ctx: &Context, args: Vec<String>) -> StResult {
+        let ctx = Self::django_context(ctx, false)?; // 设置必要的环境变量
+        Self::do_poetry_django_admin_run(&ctx, args)
     }
 
-    fn do_poetry_django_admin_run(args: Vec<String>) {
-        let cur_dir = env::current_dir().expect("获取当前目录失败");
-
+    fn do_poetry_django_admin_run(ctx: &Context, args: Vec<String>) -> StResult {
         let full_args = {
             let mut t = vec![
                 "run".to_string(),
@@ -46,68 +45,51 @@ impl Django {
             t
         };
 
-        let django_dir = cur_dir.join(Poetry::ensure_get_src_dir());
-
-        utils::switch_dir_exec(django_dir, move || Poetry::poetry_run(full_args.clone()));
+        // manage.py 在源代码目录中
+        let django_ctx = ctx.with_dir(Poetry::get_src_dir(ctx)?);
+        Poetry::poetry_run(&django_ctx, full_args)
     }
 
     /// 检测是否为 django 的项目
-    pub fn check_django_project() -> bool {
-        if !Poetry::check_poetry_project() {
+    pub fn check_django_project(ctx: &Context) -> bool {
+        if !Poetry::check_poetry_project(ctx) {
             return false;
         }
 
         // check if `repo_name`/`repo_name`/wsgi.py
         // wsgi.py 是否存在
-        let wsgi_file = {
-            let src_dir = Poetry::ensure_get_src_dir();
-            let mut cur_dir = env::current_dir().expect("获取当前工作目录失败");
-            cur_dir.push(src_dir.clone());
-            cur_dir.push(src_dir);
-            cur_dir.push("wsgi.py");
-            cur_dir
+        let src_dir = match Poetry::get_src_dir(ctx) {
+            Ok(s) => s,
+            _ => return false,
         };
-        wsgi_file.exists()
+        ctx.has_file(
+            std::path::Path::new(&src_dir)
+                .join(&src_dir)
+                .join("wsgi.py"),
+        )
     }
-}
 
-impl StTrait for Django {
-    fn name(&self) -> String {
-        String::from("django")
-    }
-
-    fn support_run(&self) -> bool {
-        Self::check_django_project()
-    }
-
-    fn do_run(&self) {
+    /// 迁移数据库 并启动开发服务器
+    fn run_server(ctx: &Context) -> StResult {
         println!("django start make migrations ...");
-        Self::poetry_django_admin_dev_run(vec!["makemigrations".to_string()]);
+        Self::poetry_django_admin_dev_run(ctx, vec!["makemigrations".to_string()])?;
         println!("django start migrate ...");
-        Self::poetry_django_admin_dev_run(vec!["migrate".to_string()]);
+        Self::poetry_django_admin_dev_run(ctx, vec!["migrate".to_string()])?;
         println!("django start run server ...");
-        Self::poetry_django_admin_dev_run(vec!["runserver".to_string()]);
-    }
-
-    fn support_lint(&self) -> bool {
-        Self::check_django_project()
-    }
-
-    fn do_lint(&self) {
-        Self::poetry_django_admin_dev_run(vec!["check".to_string()])
-    }
-
-    fn support_bump(&self) -> bool {
-        Self::check_django_project()
+        Self::poetry_django_admin_dev_run(
+            ctx,
+            utils::append_args(vec!["runserver".to_string()], &ctx.args),
+        )
     }
 
     /// 版本升级 dev,test and prod
-    fn do_bump(&self, bump: &Bump) {
+    fn bump_version(ctx: &Context, bump: &Bump) -> StResult {
         let version_file = "version.json";
 
         let old = {
             // read old version
-            let v = std::fs::read_to_string(version_file).expect("version.json 文件不存在");
+            let v = std::fs::read_to_string(ctx.path(version_file))
+                .map_err(|e| StError::bad_manifest(version_file, e))?;
             match serde_json::from_str(v.as_str()) {
                 Ok(v) => v,
                 Err(e) => {
@@ -118,8 +100,7 @@ impl StTrait for Django {
         };
 
         // parse new version
-        let new_version =
-            Poetry::get_poetry_project_version().expect("从 pyproject.toml 获取 新版本失败");
+        let new_version = Poetry::get_poetry_project_version(ctx)?;
 
         // set new version
         let new = match bump {
@@ -147,8 +128,52 @@ impl StTrait for Django {
         };
         // write to disk
         {
-            let s = serde_json::to_string_pretty(&new).expect("序列化新版本信息失败");
-            std::fs::write(version_file, s).expect("写入新版本失败");
+            let s = serde_json::to_string_pretty(&new)
+                .map_err(|e| StError::bad_manifest(version_file, e))?;
+            if ctx.dry_run {
+                println!("[dry-run] write {}:\n{}", version_file, s);
+                return Ok(());
+            }
+            std::fs::write(ctx.path(version_file), s)
+                .map_err(|e| StError::io(format!("写入 {} 失败", version_file), e))?;
         };
+        Ok(())
+    }
+}
+
+impl StTrait for Django {
+    fn name(&self) -> String {
+        String::from("django")
+    }
+
+    fn detect(&self, ctx: &Context) -> bool {
+        Self::check_django_project(ctx)
+    }
+
+    fn supports(&self, action: &Action, ctx: &Context) -> bool {
+        match action {
+            Action::Run | Action::Lint | Action::Bump(_) => Self::check_django_project(ctx),
+            _ => false,
+        }
+    }
+
+    fn run(&self, action: &Action, ctx: &Context) -> StResult {
+        match action {
+            Action::Run => Self::run_server(ctx),
+            // manage.py check 只能检测整个项目
+            Action::Lint if ctx.files_with_ext(&["py"]).is_some_and(|f| f.is_empty()) => {
+                println!("没有改动的 Python 文件, 跳过 {}", action.name());
+                Ok(())
+            }
+            Action::Lint => Self::poetry_django_admin_dev_run(
+                ctx,
+                utils::append_args(vec!["check".to_string()], &ctx.args),
+            ),
+            Action::Bump(bump) => Self::bump_version(ctx, bump),
+            _ => Err(StError::Unsupported {
+                plugin: self.name(),
+                action: action.name().to_string(),
+            }),
+        }
     }
 }
@@ -1,6 +1,6 @@
 use std::fs;
 
-use crate::public::StTrait;
+use crate::public::{Action, Context, StError, StResult, StTrait};
 use crate::utils;
 
 /// Python Poetry Build Runner
@@ -9,102 +9,108 @@ pub struct Poetry {}
 
 impl Poetry {
     #[inline]
-    pub fn check_poetry_project() -> bool {
-        let f = "pyproject.toml";
-        if !utils::check_current_dir_file_exists(f) {
+    pub fn check_poetry_project(ctx: &Context) -> bool {
+        if !ctx.has_file("pyproject.toml") {
             return false;
         }
 
-        if Self::get_poetry_config().is_none() {
-            return false;
+        match Self::get_poetry_config(ctx) {
+            Ok(Some(_)) => {}
+            Ok(None) => return false,
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
         }
 
-        if !utils::check_exe_exists("poetry") {
+        if ctx.exec_path("poetry").is_err() {
             return false;
         }
 
         true
     }
 
-    /// 保证获取到 代码 目录
-    /// 否则直接退出
-    ///
-    /// 返回的是目录名
-    pub fn ensure_get_src_dir() -> String {
-        Self::get_src_dir().expect("获取代码目录失败")
-    }
-
     /// 获取源代码的目录
     ///
     /// 返回的是目录名
     /// 例如:
     ///     django_div_node
     ///
-    pub fn get_src_dir() -> Option<String> {
-        let name = Self::get_poetry_config().map(|v| -> Option<String> {
-            if let Some(name) = v.get("name") {
-                if let Some(s) = name.as_str() {
-                    return Some(String::from(s));
-                }
-            }
-            None
-        });
+    pub fn get_src_dir(ctx: &Context) -> Result<String, StError> {
+        let name = Self::get_poetry_string(ctx, "name")?;
+        // Python 包 不允许使用 '-' 需要用 '_' 替换
+        Ok(name.replace('-', "_"))
+    }
 
-        if let Some(Some(dir)) = name {
-            // Python 包 不允许使用 '-' 需要用 '_' 替换
-            return Some(dir.replace("-", "_"));
-        }
-        None
+    pub fn get_poetry_project_version(ctx: &Context) -> Result<String, StError> {
+        Self::get_poetry_string(ctx, "version")
     }
 
-    pub fn get_poetry_project_version() -> Option<String> {
-        let config = Self::get_poetry_config()?;
-        if !config.is_table() {
-            return None;
-        }
+    /// 获取 `[tool.poetry]` 中 `key` 的值
+    fn get_poetry_string(ctx: &Context, key: &str) -> Result<String, StError> {
+        let config = Self::get_poetry_config(ctx)?
+            .ok_or_else(|| StError::bad_manifest("pyproject.toml", "缺少 [tool.poetry]"))?;
 
-        let version = config.get("version")?;
-        version.as_str().map(String::from)
+        config
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .ok_or_else(|| {
+                StError::bad_manifest("pyproject.toml", format!("缺少 tool.poetry.{}", key))
+            })
     }
 
     /// 获取 poetry 的配置
-    pub fn get_poetry_config() -> Option<toml::Value> {
+    ///
+    /// 不是 poetry 项目时 返回 `None`
+    pub fn get_poetry_config(ctx: &Context) -> Result<Option<toml::Value>, StError> {
         let f = "pyproject.toml";
 
         // extract to utils
-        let s = fs::read_to_string(f).expect("读取 pyproject.toml 失败!");
-        let v = toml::from_str::<toml::Value>(s.as_str()).expect("解析 pyproject.toml 失败");
-        if !v.is_table() {
-            return None;
-        }
+        let s = fs::read_to_string(ctx.path(f))
+            .map_err(|e| StError::io("读取 pyproject.toml 失败", e))?;
+        let v =
+            toml::from_str::<toml::Value>(s.as_str()).map_err(|e| StError::bad_manifest(f, e))?;
 
         // tool.poetry 存在 才证明是 poetry 项目
-        if let Some(tool) = v.get("tool") {
-            if !tool.is_table() {
-                return None;
-            }
-            if let Some(poetry) = tool.get("poetry") {
-                return Some(poetry.clone());
-            }
-        }
+        Ok(v.get("tool")
+            .and_then(|tool| tool.get("poetry"))
+            .filter(|poetry| poetry.is_table())
+            .cloned())
+    }
 
-        None
+    /// black / pylama 处理的路径
+    ///
+    /// 限制了文件时 只处理其中的 Python 文件, 没有 Python 文件时 返回 `None`
+    fn get_targets(ctx: &Context) -> Result<Option<Vec<String>>, StError> {
+        match ctx.files_with_ext(&["py"]) {
+            Some(files) if files.is_empty() => Ok(None),
+            Some(files) => Ok(Some(files)),
+            None => Ok(Some(vec![Self::get_src_dir(ctx)?])),
+        }
     }
 
     #[inline]
-    pub fn poetry_run(args: Vec<String>) -> bool {
-        let poetry = utils::get_exec_path("poetry");
-        utils::run_with_args(poetry, args)
+    pub fn poetry_run(ctx: &Context, args: Vec<String>) -> StResult {
+        let poetry = ctx.exec_path("poetry")?;
+        ctx.run(poetry, args)
     }
 
     /// 检测 poetry 中是否已经安装了相应的工具
-    pub fn check_poetry_tools_exists(name: &str) -> bool {
-        if !Self::poetry_run(vec![
-            "run".to_string(),
-            "which".to_string(),
-            "-as".to_string(),
-            name.to_string(),
-        ]) {
+    pub fn check_poetry_tools_exists(ctx: &Context, name: &str) -> bool {
+        let poetry = match ctx.exec_path("poetry") {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+        if !ctx.check(
+            poetry,
+            vec![
+                "run".to_string(),
+                "which".to_string(),
+                "-as".to_string(),
+                name.to_string(),
+            ],
+        ) {
             println!("{} 不存在, 请先安装", name);
             return false;
         }
@@ -117,108 +123,72 @@ impl StTrait for Poetry {
         String::from("poetry")
     }
 
-    fn support_build(&self) -> bool {
-        Self::check_poetry_project()
-    }
-
-    fn do_build(&self) {
-        Self::poetry_run(vec!["build".to_string()]);
-    }
-
-    fn support_clean(&self) -> bool {
-        false
-    }
-
-    fn do_clean(&self) {}
-
-    fn support_format(&self) -> bool {
-        if !Self::check_poetry_project() {
-            return false;
-        }
-        Self::check_poetry_tools_exists("black")
-    }
-
-    fn do_format(&self) {
-        Self::poetry_run(vec![
-            "run".to_string(),
-            "black".to_string(),
-            Self::ensure_get_src_dir(),
-        ]);
-    }
-
-    fn support_outdated(&self) -> bool {
-        Self::check_poetry_project()
-    }
-
-    fn do_outdated(&self) {
-        Self::poetry_run(vec!["show".to_string(), "-o".to_string()]);
-    }
-
-    fn support_run(&self) -> bool {
-        false
+    fn detect(&self, ctx: &Context) -> bool {
+        Self::check_poetry_project(ctx)
     }
 
-    fn do_run(&self) {}
-
-    fn support_update(&self) -> bool {
-        Self::check_poetry_project()
-    }
-
-    fn do_update(&self) {
-        Self::poetry_run(vec!["update".to_string()]);
-    }
-
-    fn support_lint(&self) -> bool {
-        if !Self::check_poetry_project() {
+    fn supports(&self, action: &Action, ctx: &Context) -> bool {
+        if !Self::check_poetry_project(ctx) {
             return false;
         }
-        Self::check_poetry_tools_exists("pylama")
-    }
-
-    fn do_lint(&self) {
-        Self::poetry_run(vec![
-            "run".to_string(),
-            "pylama".to_string(),
-            Self::ensure_get_src_dir(),
-        ]);
-    }
-
-    fn support_test(&self) -> bool {
-        if !Self::check_poetry_project() {
-            return false;
+        match action {
+            Action::Build | Action::Outdated | Action::Update | Action::Lock | Action::Publish => {
+                true
+            }
+            Action::Format { .. } => Self::check_poetry_tools_exists(ctx, "black"),
+            Action::Lint => Self::check_poetry_tools_exists(ctx, "pylama"),
+            Action::Test => Self::check_poetry_tools_exists(ctx, "pytest"),
+            _ => false,
         }
-
-        Self::check_poetry_tools_exists("pytest")
-    }
-
-    fn do_test(&self) {
-        Self::poetry_run(vec![
-            "run".to_string(),
-            "pytest".to_string(),
-            Self::ensure_get_src_dir(),
-        ]);
-    }
-
-    fn support_lock(&self) -> bool {
-        Self::check_poetry_project()
-    }
-
-    fn do_lock(&self) {
-        Self::poetry_run(vec![
-            "export".to_string(),
-            "--without-hashes".to_string(),
-            "-f".to_string(),
-            "requirements.txt".to_string(),
-            "-o".to_string(),
-            "requirements.txt".to_string(),
-        ]);
     }
 
-    fn support_publish(&self) -> bool {
-        Self::check_poetry_project()
-    }
-
-    fn do_publish(&self) {
-        Self::poetry_run(vec!["publish".to_string()]);
+    /// black、pylama 只处理改动的 Python 文件
+    fn handles_files(&self, action: &Action) -> bool {
+        matches!(action, Action::Format { .. } | Action::Lint)
+    }
+
+    fn run(&self, action: &Action, ctx: &Context) -> StResult {
+        let cmd = match action {
+            Action::Build => utils::to_args(&["build"]),
+            Action::Format { .. } | Action::Lint => {
+                let targets = match Self::get_targets(ctx)? {
+                    Some(targets) => targets,
+                    None => {
+                        println!("没有改动的 Python 文件, 跳过 {}", action.name());
+                        return Ok(());
+                    }
+                };
+                let cmd: &[&str] = match action {
+                    Action::Format { check: false } => &["run", "black"],
+                    // black 会在标准错误中 列出格式不正确的文件
+                    Action::Format { check: true } => &["run", "black", "--check", "--diff"],
+                    _ => &["run", "pylama"],
+                };
+                utils::append_args(utils::to_args(cmd), &targets)
+            }
+            Action::Outdated => utils::to_args(&["show", "-o"]),
+            Action::Update => utils::to_args(&["update"]),
+            Action::Test => vec![
+                "run".to_string(),
+                "pytest".to_string(),
+                Self::get_src_dir(ctx)?,
+            ],
+            Action::Lock => utils::to_args(&[
+                "export",
+                "--without-hashes",
+                "-f",
+                "requirements.txt",
+                "-o",
+                "requirements.txt",
+            ]),
+            Action::Publish => utils::to_args(&["publish"]),
+            _ => {
+                return Err(StError::Unsupported {
+                    plugin: self.name(),
+                    action: action.name().to_string(),
+                })
+            }
+        };
+        Self::poetry_run(ctx, utils::append_args(cmd, &ctx.args))
     }
 }
@@ -1,3 +1,5 @@
+use std::fs;
+
 use crate::public::*;
 use crate::utils;
 
@@ -7,14 +9,44 @@ pub struct Cargo {}
 
 impl Cargo {
     #[inline]
-    fn check_cargo_project(&self) -> bool {
-        utils::check_current_dir_file_exists("Cargo.toml")
+    fn check_cargo_project(&self, ctx: &Context) -> bool {
+        ctx.has_file("Cargo.toml")
     }
 
     #[inline]
-    fn cargo_run(&self, args: Vec<String>) {
-        let cargo = utils::get_exec_path("cargo");
-        utils::run_with_args(cargo, args);
+    fn cargo_run(&self, ctx: &Context, args: Vec<String>) -> StResult {
+        let cargo = ctx.exec_path("cargo")?;
+        ctx.run(cargo, args)
+    }
+
+    /// `Cargo.toml` 中的 edition, 没有设置时 和 Cargo 一样 默认为 2015
+    fn get_edition(&self, ctx: &Context) -> Result<String, StError> {
+        let f = "Cargo.toml";
+        let s =
+            fs::read_to_string(ctx.path(f)).map_err(|e| StError::io("读取 Cargo.toml 失败", e))?;
+        let v =
+            toml::from_str::<toml::Value>(s.as_str()).map_err(|e| StError::bad_manifest(f, e))?;
+
+        Ok(v.get("package")
+            .or_else(|| v.get("workspace").and_then(|w| w.get("package")))
+            .and_then(|p| p.get("edition"))
+            .and_then(|e| e.as_str())
+            .unwrap_or("2015")
+            .to_string())
+    }
+
+    /// 只格式化 `files` 中的文件
+    ///
+    /// `cargo fmt` 总是格式化整个项目, 因此直接使用 rustfmt
+    fn rustfmt_files(&self, ctx: &Context, check: bool, files: Vec<String>) -> StResult {
+        let mut args = vec!["--edition".to_string(), self.get_edition(ctx)?];
+        if check {
+            // 只列出格式不正确的文件
+            args.extend(utils::to_args(&["--check", "-l"]));
+        }
+        args = utils::append_args(args, &ctx.args);
+        args.extend(files);
+        ctx.run(ctx.exec_path("rustfmt")?, args)
     }
 }
 
@@ -23,80 +55,61 @@ impl StTrait for Cargo {
         String::from("cargo")
     }
 
-    fn support_build(&self) -> bool {
-        self.check_cargo_project()
-    }
-
-    fn do_build(&self) {
-        self.cargo_run(vec!["build".to_string()])
-    }
-
-    fn support_clean(&self) -> bool {
-        self.check_cargo_project()
-    }
-
-    fn do_clean(&self) {
-        self.cargo_run(vec!["clean".to_string()])
-    }
-
-    fn support_format(&self) -> bool {
-        self.check_cargo_project()
-    }
-
-    fn do_format(&self) {
-        self.cargo_run(vec!["fmt".to_string()])
-    }
-
-    fn support_outdated(&self) -> bool {
-        self.check_cargo_project()
-    }
-
-    fn do_outdated(&self) {
-        self.cargo_run(vec!["outdated".to_string()])
-    }
-
-    fn support_run(&self) -> bool {
-        self.check_cargo_project()
-    }
-
-    fn do_run(&self) {
-        self.cargo_run(vec!["run".to_string()])
-    }
-
-    fn support_update(&self) -> bool {
-        self.check_cargo_project()
-    }
-
-    fn do_update(&self) {
-        self.cargo_run(vec!["update".to_string()])
-    }
-
-    fn support_lint(&self) -> bool {
-        self.check_cargo_project()
-    }
-
-    fn do_lint(&self) {
-        self.cargo_run(vec!["clippy".to_string()])
-    }
-
-    fn support_test(&self) -> bool {
-        self.check_cargo_project()
-    }
-
-    fn do_test(&self) {
-        self.cargo_run(vec!["test".to_string()])
-    }
-
-    fn support_install(&self) -> bool {
-        self.check_cargo_project()
-    }
-
-    fn do_install(&self) {
-        self.cargo_run(vec![
-            "install".to_string(),
-            "--force".to_string(),
-            "--path".to_string(),
-            ".".to_string(),
-        ])
+    fn detect(&self, ctx: &Context) -> bool {
+        self.check_cargo_project(ctx)
+    }
+
+    fn supports(&self, action: &Action, ctx: &Context) -> bool {
+        match action {
+            Action::Build
+            | Action::Clean
+            | Action::Format { .. }
+            | Action::Outdated
+            | Action::Run
+            | Action::Update
+            | Action::Lint
+            | Action::Test
+            | Action::Install => self.check_cargo_project(ctx),
+            _ => false,
+        }
+    }
+
+    fn handles_files(&self, action: &Action) -> bool {
+        matches!(action, Action::Format { .. })
+    }
+
+    fn run(&self, action: &Action, ctx: &Context) -> StResult {
+        match (action, ctx.files_with_ext(&["rs"])) {
+            (Action::Format { .. } | Action::Lint, Some(files)) if files.is_empty() => {
+                println!("没有改动的 Rust 文件, 跳过 {}", action.name());
+                return Ok(());
+            }
+            (Action::Format { check }, Some(files)) => {
+                return self.rustfmt_files(ctx, *check, files);
+            }
+            // clippy 只能检测整个项目
+            _ => {}
+        }
+
+        let cmd: &[&str] = match action {
+            Action::Build => &["build"],
+            Action::Clean => &["clean"],
+            Action::Format { check: false } => &["fmt"],
+            // 只列出格式不正确的文件
+            Action::Format { check: true } => &["fmt", "--check", "--message-format", "short"],
+            Action::Outdated => &["outdated"],
+            Action::Run => &["run"],
+            Action::Update => &["update"],
+            Action::Lint => &["clippy"],
+            Action::Test => &["test"],
+            Action::Install => &["install", "--force", "--path", "."],
+            _ => {
+                return Err(StError::Unsupported {
+                    plugin: self.name(),
+                    action: action.name().to_string(),
+                })
+            }
+        };
+        self.cargo_run(ctx, utils::append_args(utils::to_args(cmd), &ctx.args))
     }
 }
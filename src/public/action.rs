@@ -0,0 +1,77 @@
+use super::bump::Bump;
+
+/// 通过插件执行的命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Build,
+    Clean,
+    /// `check` 时 只检查格式, 不修改文件
+    Format {
+        check: bool,
+    },
+    Lint,
+    Outdated,
+    Run,
+    Update,
+    Test,
+    Sync,
+    Lock,
+    Install,
+    Publish,
+    Bump(Bump),
+    /// 内置命令之外的命令, 例如外部插件提供的 `st docs`
+    Custom(String),
+}
+
+impl Action {
+    /// 所有的内置命令
+    ///
+    /// `format` 使用 `Format { check: false }`, `bump` 使用 `Bump::Dev` 表示
+    pub fn all() -> Vec<Action> {
+        vec![
+            Action::Build,
+            Action::Clean,
+            Action::Format { check: false },
+            Action::Lint,
+            Action::Outdated,
+            Action::Run,
+            Action::Update,
+            Action::Test,
+            Action::Sync,
+            Action::Lock,
+            Action::Install,
+            Action::Publish,
+            Action::Bump(Bump::Dev),
+        ]
+    }
+
+    /// 命令名称, 和命令行、配置文件中的名称一致
+    pub fn name(&self) -> &str {
+        match self {
+            Action::Build => "build",
+            Action::Clean => "clean",
+            Action::Format { .. } => "format",
+            Action::Lint => "lint",
+            Action::Outdated => "outdated",
+            Action::Run => "run",
+            Action::Update => "update",
+            Action::Test => "test",
+            Action::Sync => "sync",
+            Action::Lock => "lock",
+            Action::Install => "install",
+            Action::Publish => "publish",
+            Action::Bump(_) => "bump",
+            Action::Custom(name) => name.as_str(),
+        }
+    }
+
+    /// 根据名称查找命令, `bump` 使用 `Bump::Dev`
+    ///
+    /// 不是内置命令时 返回 `Action::Custom`
+    pub fn from_name(name: &str) -> Action {
+        Self::all()
+            .into_iter()
+            .find(|a| a.name() == name)
+            .unwrap_or_else(|| Action::Custom(name.to_string()))
+    }
+}
@@ -0,0 +1,44 @@
+use structopt::StructOpt;
+
+/// 透传给底层工具的参数
+#[derive(Debug, Default, StructOpt)]
+pub struct ExtraArgs {
+    /// `--` 之后的参数, 会原样追加到底层命令的末尾
+    #[structopt(last = true)]
+    pub args: Vec<String>,
+}
+
+/// 只处理 git 中改动的文件
+#[derive(Debug, Default, StructOpt)]
+pub struct ChangedArgs {
+    /// 只处理相对于 --base 改动的文件, 包括没有提交的改动 和新增的文件
+    #[structopt(long)]
+    pub changed: bool,
+    /// --changed 比较的分支或者提交, 例如 origin/main, 默认为 HEAD
+    #[structopt(long, requires = "changed")]
+    pub base: Option<String>,
+    /// 只处理暂存区中的文件
+    #[structopt(long, conflicts_with = "changed")]
+    pub staged: bool,
+}
+
+/// 格式化 命令的参数
+#[derive(Debug, Default, StructOpt)]
+pub struct FormatArgs {
+    /// 只检查格式 不修改文件, 格式不正确时 列出这些文件并返回非 0 的退出码
+    #[structopt(long)]
+    pub check: bool,
+    #[structopt(flatten)]
+    pub changed: ChangedArgs,
+    #[structopt(flatten)]
+    pub extra: ExtraArgs,
+}
+
+/// 代码检测 命令的参数
+#[derive(Debug, Default, StructOpt)]
+pub struct LintArgs {
+    #[structopt(flatten)]
+    pub changed: ChangedArgs,
+    #[structopt(flatten)]
+    pub extra: ExtraArgs,
+}
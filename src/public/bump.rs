@@ -15,7 +15,7 @@ pub struct Version {
 }
 
 /// 提升 版本 命令
-#[derive(Debug, StructOpt)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, StructOpt)]
 pub enum Bump {
     /// 提升 开发 环境的版本
     Dev,
@@ -24,3 +24,22 @@ pub enum Bump {
     /// 提升 线上 环境的版本
     Prod,
 }
+
+impl Bump {
+    /// 环境名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bump::Dev => "dev",
+            Bump::Test => "test",
+            Bump::Prod => "prod",
+        }
+    }
+
+    /// 根据环境名称查找
+    pub fn from_name(name: &str) -> Option<Bump> {
+        [Bump::Dev, Bump::Test, Bump::Prod]
+            .iter()
+            .copied()
+            .find(|b| b.name() == name)
+    }
+}
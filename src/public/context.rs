@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{CommandRunner, Invocation, StError, StResult, SystemRunner};
+
+/// 命令执行的上下文
+///
+/// 插件通过它获取 项目目录、环境变量 以及命令行选项,
+/// 执行命令时 使用 `Command::current_dir`、`Command::envs`, 不修改当前进程的状态
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// 项目的根目录, 命令在这个目录中执行
+    pub root: PathBuf,
+    /// 额外的环境变量, 只对通过这个上下文执行的命令生效
+    pub envs: BTreeMap<String, String>,
+    /// 输出的详细程度, 大于 0 时 打印执行的命令
+    pub verbose: u8,
+    /// 只打印命令 不实际执行
+    pub dry_run: bool,
+    /// 用户在 `--` 之后传入的参数, 插件需要把它们追加到底层命令的末尾
+    pub args: Vec<String>,
+    /// 只处理这些文件 (相对于 `root`), 为 `None` 时 处理整个项目
+    ///
+    /// 例如 `st format --changed` 时为 git 中改动的文件
+    pub files: Option<Vec<PathBuf>>,
+    /// 执行命令的方式, 默认启动子进程
+    pub runner: Arc<dyn CommandRunner>,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context {
+            root: PathBuf::new(),
+            envs: BTreeMap::new(),
+            verbose: 0,
+            dry_run: false,
+            args: vec![],
+            files: None,
+            runner: Arc::new(SystemRunner),
+        }
+    }
+}
+
+impl Context {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Context {
+            root: root.into(),
+            ..Self::default()
+        }
+    }
+
+    /// 使用 `args` 替换透传的参数
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// 增加环境变量
+    pub fn with_env<V: ToString>(mut self, key: &str, value: V) -> Self {
+        self.envs.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// 在 `dir` 中执行的上下文, 相对路径基于 `root`
+    pub fn with_dir<P: AsRef<Path>>(&self, dir: P) -> Self {
+        Context {
+            root: self.root.join(dir),
+            ..self.clone()
+        }
+    }
+
+    /// 项目中的文件路径
+    pub fn path<P: AsRef<Path>>(&self, file: P) -> PathBuf {
+        self.root.join(file)
+    }
+
+    /// 项目中是否存在 `file`
+    pub fn has_file<P: AsRef<Path>>(&self, file: P) -> bool {
+        self.path(file).exists()
+    }
+
+    /// `files` 中扩展名为 `exts` 之一的文件
+    ///
+    /// 不限制文件时 返回 `None`, 插件应该处理整个项目
+    pub fn files_with_ext(&self, exts: &[&str]) -> Option<Vec<String>> {
+        let files = self.files.as_ref()?;
+        Some(
+            files
+                .iter()
+                .filter(|f| {
+                    f.extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| exts.contains(&e))
+                })
+                .map(|f| f.display().to_string())
+                .collect(),
+        )
+    }
+
+    /// 使用 `runner` 执行命令
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// 查找可执行文件的路径
+    ///
+    /// 包含目录的名称 (例如 `./scripts/gen.sh`) 相对于 `root`, 不在 PATH 中查找
+    pub fn exec_path(&self, name: &str) -> Result<PathBuf, StError> {
+        if Path::new(name).components().count() > 1 {
+            return Ok(self.path(name));
+        }
+        self.runner.which(name)
+    }
+
+    /// 使用 args 运行 bin
+    ///
+    /// fix: 使用这个运行有可能暴露 token 出来
+    /// 因此只允许交互式的 publish 不允许
+    ///
+    /// 失败时返回 `StError::ChildFailed`, 其中包含子进程的退出码
+    ///
+    /// dry-run 模式下 只打印命令, 不执行
+    pub fn run(&self, bin: PathBuf, args: Vec<String>) -> StResult {
+        let cmd = self.invocation(bin, args);
+        if self.dry_run {
+            self.print_command("[dry-run]", &cmd);
+            self.runner.dry_run(&cmd);
+            return Ok(());
+        }
+        if self.verbose > 0 {
+            self.print_command("[verbose]", &cmd);
+        }
+        self.runner.run(&cmd)
+    }
+
+    /// 使用 args 运行 bin, 只关心是否成功
+    ///
+    /// 用于检测工具是否存在, dry-run 模式下同样会执行,
+    /// 以保证和实际运行时选择相同的插件
+    pub fn check(&self, bin: PathBuf, args: Vec<String>) -> bool {
+        self.runner.check(&self.invocation(bin, args))
+    }
+
+    /// 使用 args 运行 bin, 返回标准输出
+    ///
+    /// dry-run 模式下同样会执行
+    pub fn output(&self, bin: PathBuf, args: Vec<String>) -> Result<String, StError> {
+        self.runner.output(&self.invocation(bin, args))
+    }
+
+    /// 使用 args 运行 bin, 返回标准输出, 超过 `timeout` 时 结束命令
+    ///
+    /// 用于可能不会退出的程序 (例如外部插件), dry-run 模式下同样会执行
+    pub fn output_timeout(
+        &self,
+        bin: PathBuf,
+        args: Vec<String>,
+        timeout: Duration,
+    ) -> Result<String, StError> {
+        self.runner
+            .output_timeout(&self.invocation(bin, args), timeout)
+    }
+
+    /// 在项目目录中 使用额外的环境变量 执行 bin
+    fn invocation(&self, bin: PathBuf, args: Vec<String>) -> Invocation {
+        Invocation {
+            program: bin,
+            args,
+            cwd: self.root.clone(),
+            envs: self.envs.clone(),
+        }
+    }
+
+    /// 打印 将要执行的命令
+    fn print_command(&self, prefix: &str, cmd: &Invocation) {
+        println!("{} cwd: {}", prefix, cmd.cwd.display());
+        for (k, v) in &cmd.envs {
+            println!("{} env: {}={}", prefix, k, v);
+        }
+        println!("{} run: {}", prefix, cmd);
+    }
+}
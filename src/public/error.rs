@@ -0,0 +1,133 @@
+use std::fmt;
+use std::io;
+
+/// st 的错误
+///
+/// 每种错误对应固定的退出码, 见 `StError::exit_code`
+#[derive(Debug)]
+pub enum StError {
+    /// 找不到可执行文件
+    MissingTool(String),
+    /// 配置文件 (pyproject.toml、version.json、st.toml 等) 不存在或者无效
+    BadManifest { file: String, reason: String },
+    /// 子进程执行失败
+    ChildFailed {
+        command: String,
+        /// 被信号终止时 没有退出码
+        code: Option<i32>,
+    },
+    /// 当前目录不是命令要求的项目类型
+    NotProject(String),
+    /// 找不到项目 (子项目目录不存在, 或者目录下没有任何项目)
+    MissingProject(String),
+    /// 没有插件支持该命令
+    NoHandler,
+    /// 插件不支持该命令
+    Unsupported { plugin: String, action: String },
+    /// 配置中没有该任务
+    UnknownTask(String),
+    /// 不是内置命令、别名, 也没有插件支持 (例如输错了命令)
+    UnknownCommand {
+        name: String,
+        /// 可用的命令
+        commands: Vec<String>,
+    },
+    /// IO 错误
+    Io { context: String, source: io::Error },
+}
+
+impl StError {
+    pub fn bad_manifest<F: ToString, R: ToString>(file: F, reason: R) -> Self {
+        StError::BadManifest {
+            file: file.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn io<C: ToString>(context: C, source: io::Error) -> Self {
+        StError::Io {
+            context: context.to_string(),
+            source,
+        }
+    }
+
+    /// 进程的退出码
+    ///
+    /// 子进程失败时 使用子进程的退出码, 其他错误使用固定的退出码
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StError::ChildFailed { code, .. } => code.unwrap_or(1),
+            StError::NoHandler
+            | StError::Unsupported { .. }
+            | StError::UnknownTask(_)
+            | StError::UnknownCommand { .. } => 2,
+            StError::NotProject(_) => 3,
+            StError::MissingProject(_) => 4,
+            StError::BadManifest { .. } => 78,
+            StError::Io { .. } => 74,
+            StError::MissingTool(_) => 127,
+        }
+    }
+
+    /// 错误的类型, 用于 `--output json`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StError::MissingTool(_) => "missing_tool",
+            StError::BadManifest { .. } => "bad_manifest",
+            StError::ChildFailed { .. } => "child_failed",
+            StError::NotProject(_) => "not_project",
+            StError::MissingProject(_) => "missing_project",
+            StError::NoHandler => "no_handler",
+            StError::Unsupported { .. } => "unsupported",
+            StError::UnknownTask(_) => "unknown_task",
+            StError::UnknownCommand { .. } => "unknown_command",
+            StError::Io { .. } => "io",
+        }
+    }
+}
+
+impl fmt::Display for StError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StError::MissingTool(name) => write!(f, "找不到可执行文件: {}, 请先安装", name),
+            StError::BadManifest { file, reason } => write!(f, "{} 无效: {}", file, reason),
+            StError::ChildFailed {
+                command,
+                code: Some(code),
+            } => write!(f, "执行命令: {} 失败, 退出码: {}", command, code),
+            StError::ChildFailed {
+                command,
+                code: None,
+            } => {
+                write!(f, "执行命令: {} 失败, 被信号终止", command)
+            }
+            StError::NotProject(kind) => write!(f, "当前不是 {} 项目, 无法执行", kind),
+            StError::MissingProject(dir) => write!(f, "找不到项目: {}", dir),
+            StError::NoHandler => write!(f, "没有找到处理工具"),
+            StError::Unsupported { plugin, action } => {
+                write!(f, "插件 {} 不支持 {} 命令", plugin, action)
+            }
+            StError::UnknownTask(name) => {
+                write!(f, "找不到任务: {}, 使用 st x --list 查看所有任务", name)
+            }
+            StError::UnknownCommand { name, commands } => {
+                write!(
+                    f,
+                    "未知的命令: {}, 可用的命令: {}",
+                    name,
+                    commands.join(", ")
+                )
+            }
+            StError::Io { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for StError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -1,93 +1,63 @@
+//! 插件、命令 以及错误等公共类型
+
+mod action;
+mod args;
 pub mod bump;
+mod context;
+mod error;
+mod runner;
+
+pub use action::Action;
+pub use args::{ChangedArgs, ExtraArgs, FormatArgs, LintArgs};
+pub use context::Context;
+pub use error::StError;
+pub use runner::{CommandRunner, Invocation, RecordingRunner, SystemRunner};
+
+/// 命令执行结果
+pub type StResult = Result<(), StError>;
 
 /// 可扩展的第三方 子命令
 pub trait RunTrait {
     /// 运行 命令
-    fn run(&self);
+    fn run(&self, ctx: &Context) -> StResult;
 }
 
 /// st tools 支持
+///
+/// 插件通过 `supports` 声明支持的命令, 通过 `run` 执行命令.
+/// 新增命令 (`Action::Custom`) 不需要修改这个 trait
+///
+/// 项目目录、环境变量等 都从 `Context` 中获取, 插件不应该读取或修改当前进程的状态
 pub trait StTrait {
     /// 名称
     fn name(&self) -> String;
 
-    /// 支持 `build` 命令
-    fn support_build(&self) -> bool {
-        false
-    }
-    fn do_build(&self) {}
-
-    /// 支持 `clean` 命令
-    fn support_clean(&self) -> bool {
-        false
-    }
-    fn do_clean(&self) {}
-
-    /// 支持 `format` 命令
-    fn support_format(&self) -> bool {
-        false
-    }
-    fn do_format(&self) {}
-
-    /// 支持 `outdated` 命令
-    fn support_outdated(&self) -> bool {
-        false
-    }
-    fn do_outdated(&self) {}
-
-    /// 支持 `run` 命令
-    fn support_run(&self) -> bool {
-        false
-    }
-    fn do_run(&self) {}
-
-    /// 支持 `update` 命令
-    fn support_update(&self) -> bool {
-        false
-    }
-    fn do_update(&self) {}
-
-    /// 支持 `lint` 命令
-    /// C 程序员应该知道这是啥意思 :)
-    fn support_lint(&self) -> bool {
-        false
-    }
-    fn do_lint(&self) {}
-
-    /// 是否支持一键测试
-    fn support_test(&self) -> bool {
+    /// 项目目录是否为该插件能处理的项目
+    ///
+    /// 配置文件覆盖了命令时, 使用它代替 `supports` 判断是否执行
+    fn detect(&self, _ctx: &Context) -> bool {
         false
     }
-    fn do_test(&self) {}
 
-    /// 同步依赖
-    fn support_sync(&self) -> bool {
+    /// 项目目录是否支持 `action` 命令
+    fn supports(&self, _action: &Action, _ctx: &Context) -> bool {
         false
     }
-    fn do_sync(&self) {}
 
-    /// 锁定 依赖文件
-    fn support_lock(&self) -> bool {
+    /// 限制了文件 (`ctx.files`) 时, `action` 是否只处理其中的文件
+    ///
+    /// 只能处理整个项目的命令 (例如 clippy) 返回 `false`
+    fn handles_files(&self, _action: &Action) -> bool {
         false
     }
-    fn do_lock(&self) {}
 
-    /// 发布到中心仓库
-    fn support_publish(&self) -> bool {
-        false
-    }
-    fn do_publish(&self) {}
-
-    /// 本地安装
-    fn support_install(&self) -> bool {
-        false
+    /// 执行 `action` 命令
+    ///
+    /// `ctx.args` 为用户在 `--` 之后传入的参数, 插件需要把它们追加到底层命令的末尾
+    fn run(&self, action: &Action, _ctx: &Context) -> StResult {
+        Err(StError::Unsupported {
+            plugin: self.name(),
+            action: action.name().to_string(),
+        })
     }
-    fn do_install(&self) {}
-
-    /// 是否支持提升版本
-    fn support_bump(&self) -> bool {
-        false
-    }
-
-    fn do_bump(&self, _: &bump::Bump) {}
 }
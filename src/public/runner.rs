@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{StError, StResult};
+use crate::output::{self, Event};
+use crate::parallel;
+use crate::sub_cmd;
+use crate::utils;
+
+/// 要执行的命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    /// 工作目录
+    pub cwd: PathBuf,
+    /// 额外的环境变量
+    pub envs: BTreeMap<String, String>,
+}
+
+impl fmt::Display for Invocation {
+    /// 程序和参数, 使用 shell 的转义规则拼接
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut line = vec![self.program.display().to_string()];
+        line.extend_from_slice(&self.args);
+        write!(f, "{}", shell_words::join(line))
+    }
+}
+
+/// 执行外部命令
+///
+/// 插件通过 `Context` 使用它, 测试时 可以替换为 `RecordingRunner`
+pub trait CommandRunner: fmt::Debug + Send + Sync {
+    /// 查找可执行文件的路径
+    fn which(&self, name: &str) -> Result<PathBuf, StError>;
+
+    /// 执行命令, 继承标准输入、输出
+    fn run(&self, cmd: &Invocation) -> StResult;
+
+    /// 执行命令, 只关心是否成功
+    fn check(&self, cmd: &Invocation) -> bool;
+
+    /// 执行命令, 返回标准输出
+    fn output(&self, cmd: &Invocation) -> Result<String, StError>;
+
+    /// dry-run 模式下 代替 `run` 调用, 不执行命令
+    ///
+    /// 默认忽略, 用于记录将要执行的命令
+    fn dry_run(&self, _cmd: &Invocation) {}
+
+    /// 执行命令, 返回标准输出, 超过 `timeout` 时 结束命令
+    ///
+    /// 默认不限制时间
+    fn output_timeout(&self, cmd: &Invocation, _timeout: Duration) -> Result<String, StError> {
+        self.output(cmd)
+    }
+}
+
+/// 启动子进程 执行命令
+#[derive(Debug, Default)]
+pub struct SystemRunner;
+
+impl SystemRunner {
+    fn command(cmd: &Invocation) -> process::Command {
+        let mut c = process::Command::new(&cmd.program);
+        // 工具中再执行的 st 不是 st 的子进程
+        for name in parallel::TASK_ENVS
+            .iter()
+            .chain(&[sub_cmd::WATCH_CHILD_ENV])
+        {
+            c.env_remove(name);
+        }
+        c.args(&cmd.args).current_dir(&cmd.cwd).envs(&cmd.envs);
+        c
+    }
+}
+
+impl CommandRunner for SystemRunner {
+    fn which(&self, name: &str) -> Result<PathBuf, StError> {
+        utils::get_exec_path(name)
+    }
+
+    fn run(&self, cmd: &Invocation) -> StResult {
+        let command = format!("{} {}", cmd.program.display(), cmd.args.join(" "));
+
+        output::emit(Event::CommandSpawned {
+            command: cmd.to_string(),
+            cwd: cmd.cwd.display().to_string(),
+        });
+        let start = Instant::now();
+        let o = Self::command(cmd)
+            .status()
+            .map_err(|e| StError::io(format!("启动命令: {} 失败", command), e))?;
+        output::emit(Event::CommandExited {
+            command: cmd.to_string(),
+            exit_code: o.code(),
+            duration_ms: start.elapsed().as_millis() as u64,
+        });
+
+        if o.success() {
+            Ok(())
+        } else {
+            Err(StError::ChildFailed {
+                command,
+                code: o.code(),
+            })
+        }
+    }
+
+    fn check(&self, cmd: &Invocation) -> bool {
+        Self::command(cmd)
+            .stdout(process::Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false)
+    }
+
+    fn output(&self, cmd: &Invocation) -> Result<String, StError> {
+        let command = format!("{} {}", cmd.program.display(), cmd.args.join(" "));
+
+        let o = Self::command(cmd)
+            .stderr(process::Stdio::inherit())
+            .output()
+            .map_err(|e| StError::io(format!("启动命令: {} 失败", command), e))?;
+
+        if o.status.success() {
+            Ok(String::from_utf8_lossy(&o.stdout).into_owned())
+        } else {
+            Err(StError::ChildFailed {
+                command,
+                code: o.status.code(),
+            })
+        }
+    }
+
+    fn output_timeout(&self, cmd: &Invocation, timeout: Duration) -> Result<String, StError> {
+        let command = format!("{} {}", cmd.program.display(), cmd.args.join(" "));
+
+        let (status, stdout) =
+            utils::output_with_timeout(Self::command(cmd).stdin(process::Stdio::null()), timeout)
+                .map_err(|e| StError::io(format!("执行命令: {} 失败", command), e))?;
+
+        if status.success() {
+            Ok(String::from_utf8_lossy(&stdout).into_owned())
+        } else {
+            Err(StError::ChildFailed {
+                command,
+                code: status.code(),
+            })
+        }
+    }
+}
+
+/// 只记录 不执行命令, 用于测试
+///
+/// 所有的工具都视为已经安装 (路径即为名称), `without_tool` 指定的除外
+#[derive(Debug, Default)]
+pub struct RecordingRunner {
+    missing: Vec<String>,
+    /// 命令行 -> 标准输出
+    outputs: BTreeMap<String, String>,
+    /// 执行失败的命令行
+    failures: Vec<String>,
+    commands: Mutex<Vec<Invocation>>,
+}
+
+impl RecordingRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 视为没有安装 `name`
+    ///
+    /// 查找 `name` 会失败, 参数中包含 `name` 的检测命令 (例如 `poetry run which -as black`) 也会失败
+    pub fn without_tool(mut self, name: &str) -> Self {
+        self.missing.push(name.to_string());
+        self
+    }
+
+    /// 执行命令行为 `command_line` (见 `Invocation` 的 `Display`) 的命令时, 标准输出为 `stdout`
+    ///
+    /// 没有指定的命令 标准输出为空
+    pub fn with_output(mut self, command_line: &str, stdout: &str) -> Self {
+        self.outputs
+            .insert(command_line.to_string(), stdout.to_string());
+        self
+    }
+
+    /// 通过 `run` 执行命令行为 `command_line` 的命令时 失败, 退出码为 1
+    pub fn with_failure(mut self, command_line: &str) -> Self {
+        self.failures.push(command_line.to_string());
+        self
+    }
+
+    /// 通过 `run` 执行过的命令
+    pub fn commands(&self) -> Vec<Invocation> {
+        self.commands.lock().expect("获取命令失败").clone()
+    }
+
+    /// 通过 `run` 执行过的命令行, 见 `Invocation` 的 `Display`
+    pub fn command_lines(&self) -> Vec<String> {
+        self.commands().iter().map(|c| c.to_string()).collect()
+    }
+
+    fn is_missing(&self, cmd: &Invocation) -> bool {
+        self.missing
+            .iter()
+            .any(|m| cmd.program.as_os_str() == m.as_str() || cmd.args.contains(m))
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn which(&self, name: &str) -> Result<PathBuf, StError> {
+        if self.missing.iter().any(|m| m == name) {
+            return Err(StError::MissingTool(name.to_string()));
+        }
+        Ok(PathBuf::from(name))
+    }
+
+    fn run(&self, cmd: &Invocation) -> StResult {
+        self.commands
+            .lock()
+            .expect("保存命令失败")
+            .push(cmd.clone());
+        let line = cmd.to_string();
+        if self.failures.contains(&line) {
+            return Err(StError::ChildFailed {
+                command: line,
+                code: Some(1),
+            });
+        }
+        Ok(())
+    }
+
+    fn check(&self, cmd: &Invocation) -> bool {
+        !self.is_missing(cmd)
+    }
+
+    fn output(&self, cmd: &Invocation) -> Result<String, StError> {
+        if self.is_missing(cmd) {
+            return Err(StError::ChildFailed {
+                command: cmd.to_string(),
+                code: Some(1),
+            });
+        }
+        Ok(self
+            .outputs
+            .get(&cmd.to_string())
+            .cloned()
+            .unwrap_or_default())
+    }
+}
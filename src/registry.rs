@@ -0,0 +1,319 @@
+//! 插件注册表 以及命令的执行结果
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::cache;
+use crate::config::StConfig;
+use crate::output::{self, ErrorInfo, Event};
+use crate::plugins::{Cargo, Django, External, Npm, Poetry};
+use crate::public::{Action, CommandRunner, Context, Invocation, StError, StResult, StTrait};
+use crate::utils;
+
+/// 插件注册表
+///
+/// 插件按注册的顺序执行, 配置中的 `order` 可以调整顺序
+#[derive(Default)]
+pub struct Registry {
+    plugins: Vec<Box<dyn StTrait>>,
+}
+
+/// 单个插件的执行结果
+#[derive(Debug)]
+pub struct Outcome {
+    /// 插件名称
+    pub plugin: String,
+    pub result: StResult,
+    /// 插件实际执行的命令, dry-run 时 为将要执行的命令, 命中缓存时 为空
+    pub commands: Vec<Invocation>,
+    /// 执行用时
+    pub duration: Duration,
+    /// 是否命中缓存 跳过了执行
+    pub cached: bool,
+    /// 是否为 dry-run, 没有实际执行命令
+    pub dry_run: bool,
+}
+
+impl Outcome {
+    /// `pass`、`fail`、`cached` 或者 `dry-run`
+    pub fn status(&self) -> &'static str {
+        match &self.result {
+            Ok(()) if self.cached => "cached",
+            Ok(()) if self.dry_run => "dry-run",
+            ret => output::status(ret),
+        }
+    }
+}
+
+/// 一次命令的执行结果
+#[derive(Debug)]
+pub struct RunReport {
+    pub action: Action,
+    /// 实际执行了的插件, 按执行顺序排列
+    pub outcomes: Vec<Outcome>,
+}
+
+impl RunReport {
+    /// 是否所有执行的插件都成功了
+    pub fn is_success(&self) -> bool {
+        !self.outcomes.is_empty() && self.outcomes.iter().all(|o| o.result.is_ok())
+    }
+
+    /// 转换为单个结果
+    ///
+    /// 没有插件执行时 返回 `StError::NoHandler`, 否则返回第一个失败插件的错误.
+    /// 优先返回执行失败的错误, 其次才是 `StError::Unsupported`
+    pub fn into_result(self) -> StResult {
+        if self.outcomes.is_empty() {
+            return Err(StError::NoHandler);
+        }
+        let (unsupported, failed): (Vec<_>, Vec<_>) = self
+            .outcomes
+            .into_iter()
+            .filter_map(|o| o.result.err())
+            .partition(|e| matches!(e, StError::Unsupported { .. }));
+        match failed.into_iter().chain(unsupported).next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Registry {
+    /// 空的注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 只包含内置插件: cargo、npm、poetry、django
+    pub fn builtin() -> Self {
+        let mut r = Self::new();
+        r.register(Cargo::default())
+            .register(Npm::default())
+            .register(Poetry::default())
+            .register(Django::default());
+        r
+    }
+
+    /// 内置插件 以及 PATH 中的外部插件 (`st-<name>`)
+    pub fn discover() -> Self {
+        let mut r = Self::builtin();
+        External::discover(&r.names())
+            .into_iter()
+            .for_each(|p| r.plugins.push(Box::new(p)));
+        r
+    }
+
+    /// 注册插件
+    pub fn register<P: StTrait + 'static>(&mut self, plugin: P) -> &mut Self {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// 所有的插件
+    pub fn plugins(&self) -> &[Box<dyn StTrait>] {
+        &self.plugins
+    }
+
+    /// 所有插件的名称
+    pub fn names(&self) -> Vec<String> {
+        self.plugins.iter().map(|p| p.name()).collect()
+    }
+
+    /// 项目目录中 识别到的插件名称
+    pub fn detect(&self, ctx: &Context) -> Vec<String> {
+        self.plugins
+            .iter()
+            .filter(|p| p.detect(ctx))
+            .map(|p| p.name())
+            .collect()
+    }
+
+    /// 项目目录中 会执行 `action` 的插件, 已经按配置过滤、排序
+    pub fn supported(
+        &self,
+        config: &StConfig,
+        action: &Action,
+        ctx: &Context,
+    ) -> Vec<&dyn StTrait> {
+        config
+            .select_plugins(&self.plugins)
+            .into_iter()
+            .filter(|p| is_supported(config, action, *p, ctx))
+            .collect()
+    }
+
+    /// 在项目目录 依次使用所有支持的插件执行 `action`
+    ///
+    /// 某个插件失败之后 仍然会继续执行剩下的插件
+    ///
+    /// 配置文件中的 `plugins.<插件>.<action>` 可以覆盖或者扩展插件的命令,
+    /// `args` 会追加到命令的末尾
+    ///
+    /// 配置中开启了 `cache` 时, 输入没有变化的 build、lint、test 会跳过
+    pub fn run(&self, config: &StConfig, action: &Action, ctx: &Context) -> RunReport {
+        let outcomes = self
+            .supported(config, action, ctx)
+            .into_iter()
+            .map(|p| run_outcome(config, action, p, ctx))
+            .collect();
+        RunReport {
+            action: action.clone(),
+            outcomes,
+        }
+    }
+}
+
+/// 使用插件执行命令, 记录执行的命令 以及用时
+fn run_outcome(config: &StConfig, action: &Action, plugin: &dyn StTrait, ctx: &Context) -> Outcome {
+    let tracer = Arc::new(TracingRunner {
+        inner: ctx.runner.clone(),
+        commands: Mutex::default(),
+    });
+    let ctx = ctx.clone().with_runner(tracer.clone());
+    output::emit(Event::PluginSelected {
+        project: ctx.root.display().to_string(),
+        action: action.name().to_string(),
+        plugin: plugin.name(),
+    });
+
+    if *action == Action::Clean {
+        cache::clear(&ctx);
+    }
+    let start = Instant::now();
+    let mut cached = false;
+    let result = if config.cache {
+        cache::run_cached(&plugin.name(), action, &ctx, |ctx| {
+            run_plugin(config, action, plugin, ctx)
+        })
+        .map(|hit| cached = hit)
+    } else {
+        run_plugin(config, action, plugin, &ctx)
+    };
+    let outcome = Outcome {
+        plugin: plugin.name(),
+        result,
+        commands: tracer.commands.lock().expect("获取命令失败").clone(),
+        duration: start.elapsed(),
+        cached,
+        dry_run: ctx.dry_run,
+    };
+    output::emit(Event::PluginFinished {
+        project: ctx.root.display().to_string(),
+        action: action.name().to_string(),
+        plugin: outcome.plugin.clone(),
+        status: outcome.status(),
+        duration_ms: outcome.duration.as_millis() as u64,
+        error: outcome.result.as_ref().err().map(ErrorInfo::from),
+    });
+    outcome
+}
+
+/// 执行命令的同时 记录通过 `run` 执行的命令
+#[derive(Debug)]
+struct TracingRunner {
+    inner: Arc<dyn CommandRunner>,
+    commands: Mutex<Vec<Invocation>>,
+}
+
+impl CommandRunner for TracingRunner {
+    fn which(&self, name: &str) -> Result<PathBuf, StError> {
+        self.inner.which(name)
+    }
+
+    fn run(&self, cmd: &Invocation) -> StResult {
+        self.commands
+            .lock()
+            .expect("保存命令失败")
+            .push(cmd.clone());
+        self.inner.run(cmd)
+    }
+
+    fn check(&self, cmd: &Invocation) -> bool {
+        self.inner.check(cmd)
+    }
+
+    fn output(&self, cmd: &Invocation) -> Result<String, StError> {
+        self.inner.output(cmd)
+    }
+
+    fn output_timeout(&self, cmd: &Invocation, timeout: Duration) -> Result<String, StError> {
+        self.inner.output_timeout(cmd, timeout)
+    }
+
+    fn dry_run(&self, cmd: &Invocation) {
+        self.commands
+            .lock()
+            .expect("保存命令失败")
+            .push(cmd.clone());
+        self.inner.dry_run(cmd)
+    }
+}
+
+/// 插件是否支持 `action` 命令
+///
+/// 配置文件覆盖了命令时 不再要求插件默认使用的工具存在
+fn is_supported(config: &StConfig, action: &Action, plugin: &dyn StTrait, ctx: &Context) -> bool {
+    let overridden = config
+        .action(plugin.name().as_str(), config_key(action))
+        .is_some_and(|c| c.command.is_some());
+    if overridden || missing_check_command(config, action, plugin) {
+        plugin.detect(ctx)
+    } else {
+        plugin.supports(action, ctx)
+    }
+}
+
+/// 配置文件中 命令对应的名称
+///
+/// `format --check` 使用 `format_check`, 和会修改文件的 `format` 分开配置
+fn config_key(action: &Action) -> &str {
+    match action {
+        Action::Format { check: true } => "format_check",
+        _ => action.name(),
+    }
+}
+
+/// 配置覆盖了 `format` 的命令, 但是没有配置 `format_check`
+///
+/// 这时不能使用覆盖后的命令检查格式 (它会修改文件), 也不应该退回到插件默认的检查命令
+fn missing_check_command(config: &StConfig, action: &Action, plugin: &dyn StTrait) -> bool {
+    let name = plugin.name();
+    matches!(action, Action::Format { check: true })
+        && config.action(&name, "format_check").is_none()
+        && config
+            .action(&name, "format")
+            .is_some_and(|c| c.command.is_some())
+}
+
+/// 使用插件执行命令, 优先使用配置文件中的命令
+fn run_plugin(config: &StConfig, action: &Action, plugin: &dyn StTrait, ctx: &Context) -> StResult {
+    if missing_check_command(config, action, plugin) {
+        return Err(StError::Unsupported {
+            plugin: plugin.name(),
+            action: "format --check".to_string(),
+        });
+    }
+    let action_config = match config.action(plugin.name().as_str(), config_key(action)) {
+        Some(c) => c,
+        None => return plugin.run(action, ctx),
+    };
+
+    let full_args = utils::append_args(action_config.args.clone(), &ctx.args);
+    match &action_config.command {
+        // 不知道覆盖后的命令 能否只处理部分文件, 不能退回到处理整个项目.
+        // 插件本身也只能处理整个项目时 (例如 clippy), 和插件一样 处理整个项目
+        Some(_) if ctx.files.is_some() && plugin.handles_files(action) => {
+            Err(StError::Unsupported {
+                plugin: plugin.name(),
+                action: format!("{} (--changed / --staged, 配置中覆盖了命令)", action.name()),
+            })
+        }
+        Some(cmd) => ctx.run(
+            ctx.exec_path(cmd[0].as_str())?,
+            utils::append_args(cmd[1..].to_vec(), &full_args),
+        ),
+        None => plugin.run(action, &ctx.clone().with_args(full_args)),
+    }
+}
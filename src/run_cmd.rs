@@ -1,82 +1,270 @@
-use crate::plugins::{Cargo, Django, Npm, Poetry};
-use crate::public::{self, StTrait};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-/// 运行 `build` 命令
-pub fn run_build_cmd() {
-    do_run_all_cmd(|p| p.support_build(), |p| p.do_build())
-}
+use serde::{Deserialize, Serialize};
+
+use crate::config::StConfig;
+use crate::output::{self, Event};
+use crate::parallel;
+use crate::public::bump::Bump;
+use crate::public::{Action, Context, Invocation, StError, StResult};
+use crate::registry::{Outcome, Registry};
 
-pub fn run_clean_cmd() {
-    do_run_all_cmd(|p| p.support_clean(), |p| p.do_clean())
+/// 本次执行的所有插件步骤, `--timings` 时 写入 JSON 文件
+static STEPS: Mutex<Vec<Step>> = Mutex::new(Vec::new());
+
+/// 一个插件执行一次命令
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Step {
+    /// 项目目录
+    pub project: String,
+    pub action: String,
+    pub plugin: String,
+    /// 实际执行的命令行
+    pub commands: Vec<String>,
+    pub duration_ms: u64,
+    /// `pass`、`fail`、`cached` 或者 `dry-run`
+    pub status: String,
+    /// 失败时的退出码
+    pub exit_code: Option<i32>,
 }
 
-pub fn run_format_cmd() {
-    do_run_all_cmd(|p| p.support_format(), |p| p.do_format())
+impl Step {
+    fn new(action: &Action, ctx: &Context, outcome: &Outcome) -> Self {
+        Step {
+            project: ctx.root.display().to_string(),
+            action: action.name().to_string(),
+            plugin: outcome.plugin.clone(),
+            commands: outcome.commands.iter().map(command_line).collect(),
+            duration_ms: outcome.duration.as_millis() as u64,
+            status: outcome.status().to_string(),
+            exit_code: outcome.result.as_ref().err().map(StError::exit_code),
+        }
+    }
 }
 
-pub fn run_lint_cmd() {
-    do_run_all_cmd(|p| p.support_lint(), |p| p.do_lint())
+/// 命令行, 程序只保留文件名
+fn command_line(cmd: &Invocation) -> String {
+    let program = cmd.program.file_name().unwrap_or(cmd.program.as_os_str());
+    let mut line = vec![program.to_string_lossy().into_owned()];
+    line.extend_from_slice(&cmd.args);
+    shell_words::join(line)
 }
 
-pub fn run_outdated_cmd() {
-    do_run_all_cmd(|p| p.support_outdated(), |p| p.do_outdated())
+/// 在项目目录 使用所有支持的插件执行 `action`, 前后分别执行配置中的钩子
+///
+/// 前置钩子失败时 不再执行命令
+pub fn run_action(
+    registry: &Registry,
+    config: &StConfig,
+    action: &Action,
+    ctx: &Context,
+) -> StResult {
+    // 并发执行的子进程 只执行一个插件, 命令的开始和结束 由父进程输出
+    let events = parallel::current_task().is_none();
+    let start = Instant::now();
+    if events {
+        output::emit(Event::ActionStart {
+            project: ctx.root.display().to_string(),
+            action: action.name().to_string(),
+        });
+    }
+
+    let ret = run_pre_hook(config, action, ctx).and_then(|_| {
+        let ret = run_plugins(registry, config, action, ctx);
+        let hook_ret = run_post_hook(config, action, ctx, ret.is_ok());
+        // 命令失败时 返回命令的错误, 否则返回后置钩子的结果
+        ret.and(hook_ret)
+    });
+
+    if events {
+        output::emit(Event::action_finished(
+            &ctx.root,
+            action.name(),
+            &ret,
+            start.elapsed(),
+        ));
+    }
+    ret
 }
 
-pub fn run_run_cmd() {
-    do_run_all_cmd(|p| p.support_run(), |p| p.do_run())
+/// 在项目目录 使用所有支持的插件执行 `action`
+///
+/// 某个插件失败之后 仍然会继续执行剩下的插件,
+/// 最终返回第一个失败插件的错误
+fn run_plugins(registry: &Registry, config: &StConfig, action: &Action, ctx: &Context) -> StResult {
+    let report = registry.run(config, action, ctx);
+    report.outcomes.iter().for_each(|o| {
+        if let Err(e) = &o.result {
+            eprintln!("{} 执行失败: {}", o.plugin, e);
+        }
+    });
+
+    let steps: Vec<Step> = report
+        .outcomes
+        .iter()
+        .map(|o| Step::new(action, ctx, o))
+        .collect();
+    print_steps(&steps);
+    record_steps(steps);
+    report.into_result()
 }
 
-pub fn run_update_cmd() {
-    do_run_all_cmd(|p| p.support_update(), |p| p.do_update())
+/// 打印每个插件的命令、用时 以及结果
+fn print_steps(steps: &[Step]) {
+    if steps.is_empty() {
+        return;
+    }
+    println!();
+    println!(
+        "{:<12}{:<48}{:>10}  result",
+        "plugin", "command", "duration"
+    );
+    for step in steps {
+        let command = if step.commands.is_empty() {
+            "-".to_string()
+        } else {
+            step.commands.join(" && ")
+        };
+        let result = match (step.status.as_str(), step.exit_code) {
+            ("cached", _) => "缓存".to_string(),
+            ("dry-run", _) => "dry-run".to_string(),
+            (_, Some(code)) => format!("失败 (退出码: {})", code),
+            _ => "通过".to_string(),
+        };
+        let duration = Duration::from_millis(step.duration_ms).as_secs_f64();
+        println!(
+            "{:<12}{:<48}{:>9.2}s  {}",
+            step.plugin, command, duration, result
+        );
+    }
 }
 
-pub fn run_test_cmd() {
-    do_run_all_cmd(|p| p.support_test(), |p| p.do_test())
+/// 保存执行过的步骤
+pub fn record_steps(steps: Vec<Step>) {
+    STEPS.lock().expect("保存步骤失败").extend(steps);
 }
 
-pub fn run_sync_cmd() {
-    do_run_all_cmd(|p| p.support_sync(), |p| p.do_sync())
+/// 把执行过的所有步骤 以 JSON 数组的格式 写入 `path`
+pub fn write_steps(path: &Path) -> StResult {
+    let steps = STEPS.lock().expect("获取步骤失败");
+    let s = serde_json::to_string_pretty(&*steps)
+        .map_err(|e| StError::io("序列化执行记录失败", e.into()))?;
+    fs::write(path, s).map_err(|e| StError::io(format!("写入 {} 失败", path.display()), e))
 }
 
-pub fn run_lock_cmd() {
-    do_run_all_cmd(|p| p.support_lock(), |p| p.do_lock())
+/// 执行 `action` 的前置钩子 (`pre_<action>`), 没有配置时 直接返回成功
+pub fn run_pre_hook(config: &StConfig, action: &Action, ctx: &Context) -> StResult {
+    match config.pre_hook(action.name()) {
+        Some(hook) => run_hook(&format!("pre_{}", action.name()), &hook.command, ctx),
+        None => Ok(()),
+    }
 }
 
-pub fn run_install_cmd() {
-    do_run_all_cmd(|p| p.support_install(), |p| p.do_install())
+/// 根据命令是否成功 执行 `action` 的后置钩子 (`post_<action>`)
+///
+/// 没有配置 或者不满足执行条件时 直接返回成功
+pub fn run_post_hook(config: &StConfig, action: &Action, ctx: &Context, success: bool) -> StResult {
+    match config.post_hook(action.name()) {
+        Some(hook) if hook.when.matches(success) => {
+            run_hook(&format!("post_{}", action.name()), &hook.command, ctx)
+        }
+        _ => Ok(()),
+    }
 }
 
-pub fn run_publish_cmd() {
-    do_run_all_cmd(|p| p.support_publish(), |p| p.do_publish())
+/// 在项目目录中 执行钩子
+fn run_hook(name: &str, cmd: &[String], ctx: &Context) -> StResult {
+    println!("==> {}", name);
+    let ret = ctx
+        .exec_path(cmd[0].as_str())
+        .and_then(|bin| ctx.run(bin, cmd[1..].to_vec()));
+    if let Err(e) = &ret {
+        eprintln!("{} 执行失败: {}", name, e);
+    }
+    ret
 }
 
-pub fn run_bump_cmd(bump: &public::bump::Bump) {
-    do_run_all_cmd(|p| p.support_bump(), |p| p.do_bump(bump))
+/// 依次执行别名中的每个命令, 最后打印每个命令的结果
+///
+/// 默认在第一个失败的命令之后停止, `keep_going` 时继续执行剩下的命令
+pub fn run_alias(
+    registry: &Registry,
+    config: &StConfig,
+    steps: &[Vec<String>],
+    ctx: &Context,
+    keep_going: bool,
+) -> StResult {
+    let mut results = vec![];
+    for (idx, step) in steps.iter().enumerate() {
+        let label = shell_words::join(step);
+        println!("==> {}", label);
+
+        let (action, args) = parse_step(step);
+        let ret = run_action(
+            registry,
+            config,
+            &action,
+            &ctx.clone().with_args(args.to_vec()),
+        );
+        let failed = ret.is_err();
+        results.push((label, ret));
+
+        let rest = steps.len() - idx - 1;
+        if failed && !keep_going && rest > 0 {
+            eprintln!("跳过剩下的 {} 个命令, 使用 --keep-going 继续执行", rest);
+            break;
+        }
+    }
+    print_summary("step", results)
 }
 
-fn do_run_all_cmd<P, R>(check_fn: P, do_fn: R)
-where
-    P: Fn(&Box<dyn StTrait>) -> bool,
-    R: Fn(&Box<dyn StTrait>),
-{
-    let mut hint = false;
-    get_all_cmd().into_iter().for_each(|v| {
-        if check_fn(&v) {
-            hint = true;
-            do_fn(&v)
+/// 拆分别名中的一个命令: 命令名称 以及透传的参数
+///
+/// `bump` 之后的参数为环境名称, `format --check` 为检查模式
+pub fn parse_step(step: &[String]) -> (Action, &[String]) {
+    match (step[0].as_str(), step.get(1).map(String::as_str)) {
+        ("bump", Some(name)) => {
+            if let Some(bump) = Bump::from_name(name) {
+                return (Action::Bump(bump), &step[2..]);
+            }
         }
-    });
-    if !hint {
-        println!("没有找到处理工具");
-        std::process::exit(1);
+        ("format", Some("--check")) => return (Action::Format { check: true }, &step[2..]),
+        _ => {}
     }
+    (Action::from_name(&step[0]), &step[1..])
+}
+
+/// 项目目录中 会执行 `action` 的插件名称
+pub fn supported_plugins(
+    registry: &Registry,
+    config: &StConfig,
+    action: &Action,
+    ctx: &Context,
+) -> Vec<String> {
+    registry
+        .supported(config, action, ctx)
+        .iter()
+        .map(|p| p.name())
+        .collect()
 }
 
-fn get_all_cmd() -> Vec<Box<dyn StTrait>> {
-    vec![
-        Box::new(Cargo::default()),
-        Box::new(Npm::default()),
-        Box::new(Poetry::default()),
-        Box::new(Django::default()),
-    ]
+/// 打印每一项的结果, 返回第一个失败的结果
+pub fn print_summary(title: &str, results: Vec<(String, StResult)>) -> StResult {
+    println!();
+    println!("{:<40}result", title);
+    for (name, ret) in &results {
+        match ret {
+            Ok(()) => println!("{:<40}通过", name),
+            Err(e) => println!("{:<40}失败 (退出码: {})", name, e.exit_code()),
+        }
+    }
+
+    results
+        .into_iter()
+        .map(|(_, ret)| ret)
+        .find(|ret| ret.is_err())
+        .unwrap_or(Ok(()))
 }
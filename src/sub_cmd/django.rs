@@ -1,8 +1,7 @@
 use structopt::StructOpt;
 
 use crate::plugins::Django;
-use crate::public::RunTrait;
-use crate::utils;
+use crate::public::{Context, RunTrait, StError, StResult};
 
 /// Django 子命令
 ///
@@ -30,56 +29,59 @@ pub enum DjangoSubCmd {
 
 impl DjangoSubCmd {
     /// 收集静态文件
-    fn do_collect_static(&self) {
-        if !Django::check_django_project() {
-            eprintln!("当前不是 Django 项目, 无法执行");
-            return;
+    fn do_collect_static(&self, ctx: &Context) -> StResult {
+        if !Django::check_django_project(ctx) {
+            return Err(StError::NotProject("Django".to_string()));
         }
 
-        utils::set_env("DJANGO_COLLECT_STATIC", "1");
+        let ctx = ctx.clone().with_env("DJANGO_COLLECT_STATIC", "1");
 
         // attention:
         // it will auto switch working directory
         //
         //     poetry run python manage.py collectstatic
         //
-        Django::poetry_django_admin_prod_run(vec!["collectstatic".to_string()]);
+        Django::poetry_django_admin_prod_run(&ctx, vec!["collectstatic".to_string()])
     }
 
-    fn do_dump_data(&self) {
-        if !Django::check_django_project() {
-            eprintln!("当前不是 Django 项目, 无法执行");
-            return;
+    fn do_dump_data(&self, ctx: &Context) -> StResult {
+        if !Django::check_django_project(ctx) {
+            return Err(StError::NotProject("Django".to_string()));
         }
         // we do should export to json lines
         // https://stackoverflow.com/questions/853796/problems-with-contenttypes-when-loading-a-fixture-in-django
-        Django::poetry_django_admin_dev_run(vec![
-            "dumpdata".to_string(),
-            "--natural-foreign".to_string(),
-            "--natural-primary".to_string(),
-            "-e".to_string(),
-            "contenttypes".to_string(),
-            "-e".to_string(),
-            "auth.Permission".to_string(),
-            "-e".to_string(),
-            "sessions".to_string(),
-            "-e".to_string(),
-            "admin".to_string(),
-            // wagtail admin table is indeed not exists
-            // just ignore on export data
-            "-e".to_string(),
-            "wagtailadmin.admin".to_string(),
-            "--output".to_string(),
-            Self::get_export_filename(),
-        ]);
+        Django::poetry_django_admin_dev_run(
+            ctx,
+            vec![
+                "dumpdata".to_string(),
+                "--natural-foreign".to_string(),
+                "--natural-primary".to_string(),
+                "-e".to_string(),
+                "contenttypes".to_string(),
+                "-e".to_string(),
+                "auth.Permission".to_string(),
+                "-e".to_string(),
+                "sessions".to_string(),
+                "-e".to_string(),
+                "admin".to_string(),
+                // wagtail admin table is indeed not exists
+                // just ignore on export data
+                "-e".to_string(),
+                "wagtailadmin.admin".to_string(),
+                "--output".to_string(),
+                Self::get_export_filename(),
+            ],
+        )
     }
 
-    fn do_load_data(&self) {
-        if !Django::check_django_project() {
-            eprintln!("当前不是 Django 项目, 无法执行");
-            return;
+    fn do_load_data(&self, ctx: &Context) -> StResult {
+        if !Django::check_django_project(ctx) {
+            return Err(StError::NotProject("Django".to_string()));
         }
-        Django::poetry_django_admin_dev_run(vec!["loaddata".to_string(), Self::get_export_filename()]);
+        Django::poetry_django_admin_dev_run(
+            ctx,
+            vec!["loaddata".to_string(), Self::get_export_filename()],
+        )
     }
 
     /// 导出、导入文件名称
@@ -93,11 +95,11 @@ impl DjangoSubCmd {
 }
 
 impl RunTrait for DjangoSubCmd {
-    fn run(&self) {
+    fn run(&self, ctx: &Context) -> StResult {
         match self {
-            Self::CollectStatic => self.do_collect_static(),
-            Self::DumpData => self.do_dump_data(),
-            Self::LoadData => self.do_load_data(),
+            Self::CollectStatic => self.do_collect_static(ctx),
+            Self::DumpData => self.do_dump_data(ctx),
+            Self::LoadData => self.do_load_data(ctx),
         }
     }
 }
@@ -0,0 +1,88 @@
+use structopt::StructOpt;
+
+use crate::plugins::Poetry;
+use crate::public::{Action, Context, RunTrait, StResult};
+use crate::registry::Registry;
+use crate::utils;
+
+/// 检测结果: 支持
+const YES: &str = "✓";
+/// 检测结果: 不支持
+const NO: &str = "-";
+
+/// 显示当前目录检测到的插件 以及外部工具的版本
+#[derive(Debug, StructOpt)]
+pub struct Doctor {}
+
+impl Doctor {
+    /// 打印 插件 x 命令 的矩阵
+    fn print_plugins(&self, ctx: &Context) {
+        let registry = Registry::discover();
+        let plugins = registry.plugins();
+
+        print!("{:<10}", "action");
+        plugins.iter().for_each(|p| print!("{:<10}", p.name()));
+        println!();
+
+        print!("{:<10}", "detected");
+        plugins
+            .iter()
+            .for_each(|p| print!("{:<10}", if p.detect(ctx) { YES } else { NO }));
+        println!();
+
+        for action in Action::all() {
+            print!("{:<10}", action.name());
+            plugins.iter().for_each(|p| {
+                let supported = p.supports(&action, ctx);
+                print!("{:<10}", if supported { YES } else { NO })
+            });
+            println!();
+        }
+    }
+
+    /// 打印外部工具的版本
+    fn print_tools(&self, ctx: &Context) {
+        // black、pylama、pytest 一般安装在 poetry 的虚拟环境中
+        let in_poetry = Poetry::check_poetry_project(ctx);
+        let python_tool = |name: &'static str| -> Vec<&str> {
+            if in_poetry {
+                vec!["poetry", "run", name, "--version"]
+            } else {
+                vec![name, "--version"]
+            }
+        };
+
+        let tools: Vec<(&str, Vec<&str>)> = vec![
+            ("cargo", vec!["cargo", "--version"]),
+            ("clippy", vec!["cargo", "clippy", "--version"]),
+            ("npm", vec!["npm", "--version"]),
+            ("poetry", vec!["poetry", "--version"]),
+            ("black", python_tool("black")),
+            ("pylama", python_tool("pylama")),
+            ("pytest", python_tool("pytest")),
+        ];
+
+        println!("{:<10}version", "tool");
+        for (name, cmd) in tools {
+            let version = ctx
+                .exec_path(cmd[0])
+                .ok()
+                .and_then(|bin| ctx.output(bin, utils::to_args(&cmd[1..])).ok())
+                .and_then(|o| o.lines().next().map(|l| l.trim().to_string()));
+            println!(
+                "{:<10}{}",
+                name,
+                version.unwrap_or_else(|| "未安装".to_string())
+            );
+        }
+    }
+}
+
+impl RunTrait for Doctor {
+    fn run(&self, ctx: &Context) -> StResult {
+        self.print_plugins(ctx);
+        println!();
+        self.print_tools(ctx);
+        Ok(())
+    }
+}
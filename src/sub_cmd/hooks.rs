@@ -0,0 +1,202 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+
+use crate::config::StConfig;
+use crate::public::{Action, Context, RunTrait, StError, StResult};
+use crate::registry::Registry;
+use crate::run_cmd;
+use crate::utils;
+
+/// st 管理的钩子 都包含这一行, 用来区分用户自己的钩子
+const MARKER: &str = "# st 管理的 git 钩子";
+
+/// 安装的钩子
+const GIT_HOOKS: &[&str] = &["pre-commit", "pre-push"];
+
+/// 管理 git 钩子
+///
+/// * pre-commit: `st format --check --staged` 以及 `st lint --staged`
+/// * pre-push: `st test`
+///
+/// 已有的钩子会保存为 `<hook>.local`, 在 st 之前执行
+#[derive(Debug, StructOpt)]
+#[structopt(name = "hooks")]
+pub enum HooksCmd {
+    /// 安装 git 钩子
+    Install,
+    /// 删除 st 安装的 git 钩子, 恢复原有的钩子
+    Uninstall,
+    /// 执行 git 钩子, 由安装的钩子调用
+    #[structopt(setting = structopt::clap::AppSettings::Hidden)]
+    Run {
+        /// 钩子名称, 例如 pre-commit
+        hook: String,
+    },
+}
+
+impl HooksCmd {
+    /// git 钩子所在的目录, 会考虑 `core.hooksPath`
+    fn hooks_dir(ctx: &Context) -> Result<PathBuf, StError> {
+        let out = ctx.output(
+            ctx.exec_path("git")?,
+            utils::to_args(&["rev-parse", "--git-path", "hooks"]),
+        )?;
+        Ok(ctx.path(out.trim()))
+    }
+
+    /// 原有的钩子 保存的位置
+    fn local_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".local");
+        path.with_file_name(name)
+    }
+
+    /// 是否为 st 安装的钩子
+    fn is_managed(path: &Path) -> bool {
+        fs::read_to_string(path)
+            .map(|s| s.contains(MARKER))
+            .unwrap_or(false)
+    }
+
+    /// 钩子脚本: 先执行原有的钩子, 再在项目目录中执行 `st hooks run <hook>`
+    ///
+    /// git 在仓库的根目录执行钩子, 项目在子目录中时 需要指定项目目录
+    fn script(ctx: &Context, hook: &str) -> Result<String, StError> {
+        let exe = std::env::current_exe().map_err(|e| StError::io("获取 st 的路径失败", e))?;
+        Ok(format!(
+            r#"#!/bin/sh
+{marker}, 使用 st hooks uninstall 删除
+# 原有的钩子保存在 {hook}.local 中, 会先执行
+local_hook="$(dirname "$0")/{hook}.local"
+if [ -x "$local_hook" ]; then
+    "$local_hook" "$@" || exit $?
+fi
+exec {exe} -C {root} hooks run {hook}
+"#,
+            marker = MARKER,
+            hook = hook,
+            exe = shell_words::quote(&exe.display().to_string()),
+            root = shell_words::quote(&ctx.root.display().to_string()),
+        ))
+    }
+
+    fn install(ctx: &Context) -> StResult {
+        let dir = Self::hooks_dir(ctx)?;
+        for hook in GIT_HOOKS {
+            let path = dir.join(hook);
+            let local = Self::local_path(&path);
+            let script = Self::script(ctx, hook)?;
+
+            // 已有的钩子 不是 st 安装的, 保存下来 在 st 之前执行
+            let chain = path.exists() && !Self::is_managed(&path);
+            if chain && local.exists() {
+                return Err(StError::io(
+                    format!("保存已有的钩子 {} 失败", path.display()),
+                    io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} 已经存在", local.display()),
+                    ),
+                ));
+            }
+            if ctx.dry_run {
+                if chain {
+                    println!("[dry-run] rename {} -> {}", path.display(), local.display());
+                }
+                println!("[dry-run] write {}:\n{}", path.display(), script);
+                continue;
+            }
+
+            fs::create_dir_all(&dir)
+                .map_err(|e| StError::io(format!("创建 {} 失败", dir.display()), e))?;
+            if chain {
+                fs::rename(&path, &local)
+                    .map_err(|e| StError::io(format!("保存 {} 失败", path.display()), e))?;
+                println!("已有的 {} 钩子 保存为 {}", hook, local.display());
+            }
+            fs::write(&path, script)
+                .map_err(|e| StError::io(format!("写入 {} 失败", path.display()), e))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+                    .map_err(|e| StError::io(format!("设置 {} 权限失败", path.display()), e))?;
+            }
+            println!("已安装 {} 钩子", hook);
+        }
+        Ok(())
+    }
+
+    fn uninstall(ctx: &Context) -> StResult {
+        let dir = Self::hooks_dir(ctx)?;
+        for hook in GIT_HOOKS {
+            let path = dir.join(hook);
+            let local = Self::local_path(&path);
+            if !Self::is_managed(&path) {
+                if path.exists() {
+                    println!("{} 不是 st 安装的钩子, 跳过", path.display());
+                }
+                continue;
+            }
+            if ctx.dry_run {
+                println!("[dry-run] remove {}", path.display());
+                if local.exists() {
+                    println!("[dry-run] rename {} -> {}", local.display(), path.display());
+                }
+                continue;
+            }
+
+            fs::remove_file(&path)
+                .map_err(|e| StError::io(format!("删除 {} 失败", path.display()), e))?;
+            if local.exists() {
+                fs::rename(&local, &path)
+                    .map_err(|e| StError::io(format!("恢复 {} 失败", path.display()), e))?;
+                println!("已恢复原有的 {} 钩子", hook);
+            }
+            println!("已删除 {} 钩子", hook);
+        }
+        Ok(())
+    }
+
+    /// 执行钩子对应的命令, 任意命令失败时 停止执行
+    fn run_hook(ctx: &Context, hook: &str) -> StResult {
+        let config = StConfig::load(&ctx.root)?;
+        let (actions, staged) = match hook {
+            "pre-commit" => (vec![Action::Format { check: true }, Action::Lint], true),
+            "pre-push" => (vec![Action::Test], false),
+            _ => {
+                println!("st 不处理 {} 钩子", hook);
+                return Ok(());
+            }
+        };
+
+        let registry = Registry::discover();
+        let mut ctx = ctx.clone();
+        if staged {
+            ctx.files = Some(utils::git::staged_files(&ctx)?);
+        }
+        for action in &actions {
+            println!("==> {}", action.name());
+            match run_cmd::run_action(&registry, &config, action, &ctx) {
+                // 项目不支持的命令 不应该阻止提交
+                Err(StError::NoHandler) => println!("没有插件支持 {}, 跳过", action.name()),
+                // 例如配置中覆盖了命令, 不能只处理暂存的文件
+                Err(e @ StError::Unsupported { .. }) => eprintln!("警告: {}, 跳过", e),
+                ret => ret?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RunTrait for HooksCmd {
+    fn run(&self, ctx: &Context) -> StResult {
+        match self {
+            HooksCmd::Install => Self::install(ctx),
+            HooksCmd::Uninstall => Self::uninstall(ctx),
+            HooksCmd::Run { hook } => Self::run_hook(ctx, hook),
+        }
+    }
+}
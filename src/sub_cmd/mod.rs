@@ -1,3 +1,12 @@
 mod django;
+mod doctor;
+mod hooks;
+mod task;
+mod watch;
 
 pub use django::DjangoSubCmd;
+pub use doctor::Doctor;
+pub use hooks::HooksCmd;
+pub use task::TaskCmd;
+pub use watch::WatchCmd;
+pub(crate) use watch::WATCH_CHILD_ENV;
@@ -0,0 +1,79 @@
+use structopt::StructOpt;
+
+use crate::config::{StConfig, TaskConfig};
+use crate::public::{Context, RunTrait, StResult};
+use crate::utils;
+
+/// 执行配置文件 `[tasks]` 中的任务
+///
+/// 先按依赖关系 执行 `depends_on` 中的任务, 任意任务失败时 停止执行
+#[derive(Debug, StructOpt)]
+pub struct TaskCmd {
+    /// 列出所有任务
+    #[structopt(long, short = "l")]
+    list: bool,
+    /// 任务名称
+    #[structopt(required_unless = "list")]
+    task: Option<String>,
+    /// 追加到任务命令末尾的参数 (不会传给依赖的任务)
+    #[structopt(last = true)]
+    args: Vec<String>,
+}
+
+impl TaskCmd {
+    /// 打印所有任务
+    fn print_tasks(config: &StConfig) {
+        if config.tasks.is_empty() {
+            println!("没有配置任务, 请在 st 配置的 [tasks] 中添加");
+            return;
+        }
+        println!("{:<20}{:<30}description", "task", "depends_on");
+        for (name, task) in &config.tasks {
+            println!(
+                "{:<20}{:<30}{}",
+                name,
+                task.depends_on.join(", "),
+                task.description.as_deref().unwrap_or_default()
+            );
+        }
+    }
+
+    /// 执行单个任务, 不包括它依赖的任务
+    fn run_task(ctx: &Context, task: &TaskConfig, args: &[String]) -> StResult {
+        let cmd = match &task.command {
+            Some(cmd) => cmd,
+            None => return Ok(()),
+        };
+
+        let mut ctx = match &task.cwd {
+            Some(cwd) => ctx.with_dir(cwd),
+            None => ctx.clone(),
+        };
+        ctx.envs.extend(task.env.clone());
+
+        ctx.run(
+            ctx.exec_path(cmd[0].as_str())?,
+            utils::append_args(cmd[1..].to_vec(), args),
+        )
+    }
+}
+
+impl RunTrait for TaskCmd {
+    fn run(&self, ctx: &Context) -> StResult {
+        let config = StConfig::load(&ctx.root)?;
+        let name = match &self.task {
+            Some(name) if !self.list => name,
+            _ => {
+                Self::print_tasks(&config);
+                return Ok(());
+            }
+        };
+
+        for task_name in config.task_plan(name)? {
+            println!("==> {}", task_name);
+            let args: &[String] = if task_name == *name { &self.args } else { &[] };
+            Self::run_task(ctx, &config.tasks[&task_name], args)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use structopt::StructOpt;
+
+use crate::output;
+use crate::public::{Context, StError, StResult};
+use crate::utils;
+
+/// 监视文件时 启动的子进程, 直接执行命令
+pub(crate) const WATCH_CHILD_ENV: &str = "ST_WATCH_CHILD";
+
+/// 检查文件改动的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// 文件在这段时间内 没有继续改动时 才重新执行, 避免保存多个文件时 执行多次
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// 停止子进程时 等待它退出的时间, 超时后 强制结束
+const KILL_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 文件改动时 重新执行命令
+///
+/// 例如 `st watch test`、`st watch run`、`st watch format --check`
+///
+/// 忽略 `target`、`node_modules`、`__pycache__` 以及隐藏的目录,
+/// 长时间运行的命令 (例如 `cargo run`、Django 的 `runserver`) 会先停止 再重新启动
+#[derive(Debug, StructOpt)]
+#[structopt(setting = structopt::clap::AppSettings::TrailingVarArg)]
+pub struct WatchCmd {
+    /// 要执行的命令 以及参数
+    #[structopt(required = true, allow_hyphen_values = true)]
+    cmd: Vec<String>,
+}
+
+impl WatchCmd {
+    /// 当前进程 是否为监视文件时启动的子进程
+    pub fn is_child() -> bool {
+        env::var_os(WATCH_CHILD_ENV).is_some()
+    }
+
+    /// 子进程中 要执行的命令 以及参数
+    ///
+    /// 监视文件的进程 返回 `None`
+    pub fn child_step(&self) -> Option<&[String]> {
+        if Self::is_child() {
+            Some(&self.cmd)
+        } else {
+            None
+        }
+    }
+
+    /// 监视 `ctx.root` 中的文件, 改动时 重新启动子进程执行命令
+    ///
+    /// 子进程使用和当前进程相同的命令行参数, 按 Ctrl-C 退出
+    pub fn watch(&self, ctx: &Context) -> StResult {
+        let exe = env::current_exe().map_err(|e| StError::io("获取 st 的路径失败", e))?;
+        let label = shell_words::join(&self.cmd);
+        signal::install();
+
+        let mut snapshot = scan(&ctx.root);
+        loop {
+            println!("==> {}", label);
+            let mut child = Some(spawn(&exe)?);
+
+            let changed = loop {
+                thread::sleep(POLL_INTERVAL);
+                if signal::stopped() {
+                    if let Some(c) = child.as_mut() {
+                        stop(c);
+                    }
+                    return Ok(());
+                }
+
+                // 命令执行完成后 继续等待文件改动
+                let exited = match child.as_mut().map(Child::try_wait) {
+                    Some(Ok(Some(status))) if status.success() => {
+                        println!("==> {} 执行成功, 等待文件改动", label);
+                        true
+                    }
+                    Some(Ok(Some(status))) => {
+                        println!(
+                            "==> {} 执行失败 (退出码: {}), 等待文件改动",
+                            label,
+                            status.code().unwrap_or(1)
+                        );
+                        true
+                    }
+                    Some(Err(e)) => {
+                        eprintln!("等待子进程失败: {}", e);
+                        true
+                    }
+                    Some(Ok(None)) | None => false,
+                };
+                if exited {
+                    child = None;
+                }
+
+                let current = scan(&ctx.root);
+                if let Some(path) = first_change(&snapshot, &current) {
+                    snapshot = settle(&ctx.root, current);
+                    break path;
+                }
+            };
+
+            if let Some(c) = child.as_mut() {
+                stop(c);
+            }
+            let name = changed.strip_prefix(&ctx.root).unwrap_or(&changed);
+            println!();
+            println!("==> {} 已改动, 重新执行", name.display());
+        }
+    }
+}
+
+/// 启动子进程执行命令
+///
+/// unix 下 子进程使用单独的进程组, 停止时 可以一起结束它启动的进程.
+/// 后台进程组读取终端时 会收到 SIGTTIN 被暂停, 所以子进程的标准输入为空
+fn spawn(exe: &Path) -> Result<Child, StError> {
+    let mut cmd = Command::new(exe);
+    cmd.args(env::args_os().skip(1))
+        .env(WATCH_CHILD_ENV, "1")
+        .stdin(Stdio::null())
+        .stdout(output::child_stdout());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    cmd.spawn().map_err(|e| StError::io("启动子进程失败", e))
+}
+
+/// 停止子进程 以及它启动的进程, 超时后 强制结束
+fn stop(child: &mut Child) {
+    if matches!(child.try_wait(), Ok(Some(_))) {
+        return;
+    }
+    signal::terminate(child);
+    let start = Instant::now();
+    while start.elapsed() < KILL_TIMEOUT {
+        if !matches!(child.try_wait(), Ok(None)) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    signal::kill(child);
+    let _ = child.wait();
+}
+
+/// 文件 -> 修改时间
+type Snapshot = BTreeMap<PathBuf, SystemTime>;
+
+/// 等待文件不再改动, 返回最后的状态
+fn settle(root: &Path, mut snapshot: Snapshot) -> Snapshot {
+    loop {
+        thread::sleep(DEBOUNCE);
+        let current = scan(root);
+        if current == snapshot {
+            return snapshot;
+        }
+        snapshot = current;
+    }
+}
+
+/// 第一个新增、修改 或者删除的文件
+fn first_change(old: &Snapshot, new: &Snapshot) -> Option<PathBuf> {
+    new.iter()
+        .find(|(path, time)| old.get(*path) != Some(time))
+        .or_else(|| old.iter().find(|(path, _)| !new.contains_key(*path)))
+        .map(|(path, _)| path.clone())
+}
+
+/// `root` 中所有文件的修改时间
+fn scan(root: &Path) -> Snapshot {
+    utils::project_files(root)
+        .into_iter()
+        .filter_map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            Some((path, modified))
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+mod signal {
+    use std::process::Child;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static STOPPED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn on_signal(_: libc::c_int) {
+        STOPPED.store(true, Ordering::SeqCst);
+    }
+
+    /// 子进程不在前台进程组, 收不到 Ctrl-C, 由当前进程负责停止它
+    pub fn install() {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        unsafe {
+            libc::signal(libc::SIGINT, handler);
+            libc::signal(libc::SIGTERM, handler);
+        }
+    }
+
+    /// 是否收到了 Ctrl-C (或者 SIGTERM)
+    pub fn stopped() -> bool {
+        STOPPED.load(Ordering::SeqCst)
+    }
+
+    /// 通知子进程的进程组 退出
+    pub fn terminate(child: &mut Child) {
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), libc::SIGTERM);
+        }
+    }
+
+    /// 强制结束子进程的进程组
+    pub fn kill(child: &mut Child) {
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod signal {
+    use std::process::Child;
+
+    /// 子进程和当前进程在同一个控制台, 会一起收到 Ctrl-C
+    pub fn install() {}
+
+    pub fn stopped() -> bool {
+        false
+    }
+
+    pub fn terminate(_: &mut Child) {}
+
+    pub fn kill(child: &mut Child) {
+        let _ = child.kill();
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use crate::public::{Context, StError};
+
+/// 在项目目录中执行 git, 返回标准输出
+fn git_output(ctx: &Context, args: &[&str]) -> Result<String, StError> {
+    ctx.output(ctx.exec_path("git")?, super::to_args(args))
+}
+
+/// `-z` 输出的文件列表, 使用 NUL 分隔
+///
+/// 不使用 `-z` 时 git 会给非 ASCII 的路径加上引号 并转义 (例如 `"\346\250\241.py"`)
+fn parse_files(output: &str) -> Vec<PathBuf> {
+    output
+        .split('\0')
+        .filter(|f| !f.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// 相对于 `base` 改动的文件 (包括没有提交的改动 和新增的文件)
+///
+/// 和 `base` 的分叉点比较, 因此 `base` 上的新提交不算改动.
+/// 只包含项目目录中 没有被删除的文件, 路径相对于项目目录
+pub fn changed_files(ctx: &Context, base: &str) -> Result<Vec<PathBuf>, StError> {
+    let fork_point = git_output(ctx, &["merge-base", base, "HEAD"])?;
+    let mut files = parse_files(&git_output(
+        ctx,
+        &[
+            "diff",
+            "--name-only",
+            "-z",
+            "--relative",
+            "--diff-filter=ACMR",
+            fork_point.trim(),
+        ],
+    )?);
+    for f in parse_files(&git_output(
+        ctx,
+        &["ls-files", "-z", "--others", "--exclude-standard"],
+    )?) {
+        if !files.contains(&f) {
+            files.push(f);
+        }
+    }
+    Ok(files)
+}
+
+/// 暂存区中的文件, 路径相对于项目目录
+pub fn staged_files(ctx: &Context) -> Result<Vec<PathBuf>, StError> {
+    Ok(parse_files(&git_output(
+        ctx,
+        &[
+            "diff",
+            "--cached",
+            "--name-only",
+            "-z",
+            "--relative",
+            "--diff-filter=ACMR",
+        ],
+    )?))
+}
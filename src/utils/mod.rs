@@ -1,78 +1,203 @@
 use std::env;
-use std::path::PathBuf;
-use std::process;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::public::StError;
+
+pub mod git;
 
 /// 获取 执行 代码的绝对路径
-pub fn get_exec_path(name: &str) -> PathBuf {
-    match which::which(name) {
-        Ok(o) => o,
-        Err(e) => {
-            eprintln!("获取可执行文件: {} 失败, 错误原因: {:?}", name, e);
-            process::exit(1)
+pub fn get_exec_path(name: &str) -> Result<PathBuf, StError> {
+    which::which(name).map_err(|_| StError::MissingTool(name.to_string()))
+}
+
+/// `&[&str]` 转换为命令行参数
+pub fn to_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+/// 把用户透传的参数 追加到 `args` 的末尾
+pub fn append_args(mut args: Vec<String>, extra: &[String]) -> Vec<String> {
+    args.extend_from_slice(extra);
+    args
+}
+
+/// 执行 `cmd` 并读取标准输出, 超过 `timeout` 时 结束子进程, 返回 `TimedOut` 错误
+pub fn output_with_timeout(
+    cmd: &mut Command,
+    timeout: Duration,
+) -> io::Result<(ExitStatus, Vec<u8>)> {
+    let mut child = cmd.stdout(Stdio::piped()).spawn()?;
+    // 单独读取标准输出, 避免输出较多时 子进程阻塞在写入上
+    let mut stdout = child.stdout.take().expect("没有获取到标准输出");
+    let reader = thread::spawn(move || {
+        let mut buf = vec![];
+        stdout.read_to_end(&mut buf).map(|_| buf)
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
         }
-    }
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} 秒内没有退出", timeout.as_secs_f32()),
+            ));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let stdout = reader
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("读取标准输出失败")))?;
+    Ok((status, stdout))
 }
 
-/// 检测 指定的 可执行文件是否存在
-pub fn check_exe_exists(name: &str) -> bool {
-    which::which(name).is_ok()
+/// 可以识别为项目根目录的文件
+const PROJECT_MARKERS: [&str; 4] = ["st.toml", "Cargo.toml", "package.json", "pyproject.toml"];
+
+/// 从 `start` 开始 逐级向上查找项目的根目录
+///
+/// 返回最近的包含 `PROJECT_MARKERS` 的目录,
+/// 找不到时 返回 git 仓库的根目录 (不会越过 git 仓库)
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        if PROJECT_MARKERS.iter().any(|f| dir.join(f).is_file()) {
+            return Some(dir.to_path_buf());
+        }
+        if dir.join(".git").exists() {
+            return Some(dir.to_path_buf());
+        }
+    }
+    None
 }
 
-/// 使用 args 运行 bin
+/// 查找子项目时 忽略的目录
+pub const IGNORE_DIRS: [&str; 6] = [
+    "target",
+    "node_modules",
+    "__pycache__",
+    ".git",
+    ".venv",
+    "venv",
+];
+
+/// 查找子项目的最大目录深度
+const MAX_PROJECT_DEPTH: usize = 4;
+
+/// 查找 `root` 下面的所有项目 (包括 `root` 自身)
 ///
-/// fix: 使用这个运行有可能暴露 token 出来
-/// 因此只允许交互式的 publish 不允许
-pub fn run_with_args(bin: PathBuf, args: Vec<String>) -> bool {
-    let bin_s = bin.to_str().map(String::from).expect("bin is unknown");
-
-    let failure_msg = format!("执行命令: {} {} 失败!", bin_s, args.join(" "));
-
-    let o = process::Command::new(bin)
-        .args(&args)
-        .status()
-        .unwrap_or_else(|e| panic!("msg: {}, error: {}", failure_msg, e));
-
-    if o.success() {
-        true
-    } else {
-        eprintln!("{}", failure_msg);
-        false
+/// 找到一个项目之后 不会继续查找它里面的目录,
+/// 例如 Cargo workspace 的成员 由 workspace 自身处理.
+/// `root` 是 Cargo workspace 时 同样不查找子目录
+pub fn find_sub_projects(root: &Path) -> Vec<PathBuf> {
+    if is_cargo_workspace(root) {
+        return vec![root.to_path_buf()];
     }
+    let mut projects = vec![];
+    if is_project_dir(root) {
+        projects.push(root.to_path_buf());
+    }
+    collect_sub_projects(root, 1, &mut projects);
+    projects
 }
 
-/// 检测当前目录是否存在指定的文件
-pub fn check_current_dir_file_exists(file: &str) -> bool {
-    return match env::current_dir() {
-        Ok(d) => {
-            let dir_path = d.as_path();
-            let file_path = dir_path.join(file);
-            file_path.exists()
-        }
+/// `Cargo.toml` 中是否有 `[workspace]`
+fn is_cargo_workspace(dir: &Path) -> bool {
+    std::fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|s| s.parse::<toml::Value>().ok())
+        .is_some_and(|v| v.get("workspace").is_some())
+}
+
+fn collect_sub_projects(dir: &Path, depth: usize, projects: &mut Vec<PathBuf>) {
+    if depth > MAX_PROJECT_DEPTH {
+        return;
+    }
+    let mut entries: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(d) => d.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
         Err(e) => {
-            eprintln!("获取当前路径失败: {:?} ", e);
-            false
+            eprintln!("读取目录 {} 失败: {}", dir.display(), e);
+            return;
+        }
+    };
+    entries.sort();
+
+    for path in entries {
+        if !path.is_dir() || is_ignored_dir(&path) {
+            continue;
         }
+        if is_project_dir(&path) {
+            projects.push(path);
+        } else {
+            collect_sub_projects(&path, depth + 1, projects);
+        }
+    }
+}
+
+/// 是否为需要忽略的目录 (包括隐藏目录)
+pub fn is_ignored_dir(path: &Path) -> bool {
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name.starts_with('.') || IGNORE_DIRS.contains(&name),
+        None => false,
+    }
+}
+
+/// `root` 中的所有文件, 按路径排序
+///
+/// 跳过需要忽略的目录、隐藏文件 以及编辑器的临时文件 (例如 vim 的 `.main.rs.swp`、`main.rs~`)
+pub fn project_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    collect_files(root, &mut files);
+    files.sort();
+    files
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(d) => d,
+        Err(_) => return,
     };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+        if file_type.is_dir() {
+            if !is_ignored_dir(&path) {
+                collect_files(&path, files);
+            }
+        } else if !is_ignored_file(&path) {
+            files.push(path);
+        }
+    }
 }
 
-/// 设置环境
-pub fn set_env<S>(key: &str, v: S)
-where
-    S: ToString,
-{
-    println!("set env: {}={}", key, v.to_string());
-    std::env::set_var(key, v.to_string())
+fn is_ignored_file(path: &Path) -> bool {
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name.starts_with('.') || name.ends_with('~'),
+        None => false,
+    }
+}
+
+/// 目录中是否有项目的配置文件
+///
+/// 只有 `st.toml` 的目录 (例如 monorepo 的根目录) 不算项目
+fn is_project_dir(dir: &Path) -> bool {
+    PROJECT_MARKERS
+        .iter()
+        .filter(|f| **f != "st.toml")
+        .any(|f| dir.join(f).is_file())
 }
 
-/// 切换到 `work_dir` 执行函数
-/// 函数执行完成之后 在切换回来
-pub fn switch_dir_exec<F, R>(work_dir: PathBuf, func: F) -> R
-where
-    F: Fn() -> R,
-{
-    let cur_dir = std::env::current_dir().expect("获取当前目录失败");
-    std::env::set_current_dir(work_dir).expect("设置工作目录失败");
-    let ret = func();
-    std::env::set_current_dir(cur_dir).expect("还原工作目录失败");
-    ret
+/// 获取当前目录
+pub fn current_dir() -> Result<PathBuf, StError> {
+    env::current_dir().map_err(|e| StError::io("获取当前目录失败", e))
 }
@@ -0,0 +1,355 @@
+//! 通过 st 的可执行文件 测试命令行
+//!
+//! 只使用 `--dry-run` 或者不需要外部工具的命令 (`sh`、`git` 除外)
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use common::temp_dir;
+
+fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+/// 在 `dir` 中执行 st
+fn st(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_st"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+fn stdout(o: &Output) -> String {
+    String::from_utf8_lossy(&o.stdout).into_owned()
+}
+
+#[test]
+fn recursive_workspace_runs_once() {
+    let dir = temp_dir("workspace");
+    write(
+        &dir.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/a\"]\n",
+    );
+    write(
+        &dir.join("crates").join("a").join("Cargo.toml"),
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n",
+    );
+
+    let o = st(&dir, &["-r", "--dry-run", "test"]);
+    assert!(o.status.success(), "{:?}", o);
+    let out = stdout(&o);
+    assert_eq!(out.matches("run: ").count(), 1, "{}", out);
+    assert!(out.contains(&format!("cwd: {}", dir.display())), "{}", out);
+}
+
+#[test]
+fn recursive_monorepo_finds_members() {
+    let dir = temp_dir("monorepo");
+    write(&dir.join("st.toml"), "");
+    for name in ["a", "b"] {
+        write(
+            &dir.join(name).join("Cargo.toml"),
+            "[package]\nname = \"x\"\nversion = \"0.1.0\"\n",
+        );
+    }
+
+    let o = st(&dir, &["-r", "--dry-run", "test"]);
+    assert!(o.status.success(), "{:?}", o);
+    assert_eq!(stdout(&o).matches("run: ").count(), 2);
+
+    // 根目录本身是项目 (不是 workspace) 时 仍然查找子项目
+    write(
+        &dir.join("Cargo.toml"),
+        "[package]\nname = \"root\"\nversion = \"0.1.0\"\n",
+    );
+    let o = st(&dir, &["-r", "--dry-run", "test"]);
+    assert!(o.status.success(), "{:?}", o);
+    assert_eq!(stdout(&o).matches("run: ").count(), 3, "{}", stdout(&o));
+}
+
+/// 以 st 子进程的身份 执行 `args`, 返回 cargo 插件执行的工具 看到的环境变量
+///
+/// `envs` 根据临时目录 返回 st 子进程的环境变量
+fn tool_envs<F>(name: &str, args: &[&str], envs: F) -> String
+where
+    F: Fn(&Path) -> Vec<(&'static str, PathBuf)>,
+{
+    let dir = temp_dir(name);
+    write(
+        &dir.join("Cargo.toml"),
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n",
+    );
+    write(
+        &dir.join("st.toml"),
+        "[plugins.cargo]\ntest = \"sh -c 'env > env.txt'\"\n",
+    );
+
+    let o = Command::new(env!("CARGO_BIN_EXE_st"))
+        .args(args)
+        .current_dir(&dir)
+        .envs(envs(&dir))
+        .output()
+        .unwrap();
+    assert!(o.status.success(), "{:?}", o);
+    fs::read_to_string(dir.join("env.txt")).unwrap()
+}
+
+#[test]
+fn child_envs_not_leaked_to_tools() {
+    // 并发执行时 父进程启动的 st 子进程
+    let envs = tool_envs("task_envs", &["test"], |dir| {
+        vec![
+            ("ST_TASK_DIR", dir.to_path_buf()),
+            ("ST_TASK_PLUGIN", PathBuf::from("cargo")),
+            ("ST_TASK_STEPS", dir.join("steps.json")),
+        ]
+    });
+    assert!(!envs.contains("ST_TASK_"), "{}", envs);
+
+    // 监视文件时 启动的 st 子进程
+    let envs = tool_envs("watch_envs", &["watch", "test"], |_| {
+        vec![("ST_WATCH_CHILD", PathBuf::from("1"))]
+    });
+    assert!(!envs.contains("ST_WATCH_CHILD"), "{}", envs);
+}
+
+#[cfg(unix)]
+#[test]
+fn external_plugins_need_handshake() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("external");
+    let bin = dir.join("bin");
+    let script = |name: &str, body: &str| {
+        let path = bin.join(name);
+        write(&path, &format!("#!/bin/sh\n{}\n", body));
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    };
+    script(
+        "st-good",
+        r#"case "$1" in
+    --st-plugin) echo '{"st_plugin": true}' ;;
+    detect) echo '{"detected": true}' ;;
+    *) echo '{"supported": false}' ;;
+esac"#,
+    );
+    // 例如 stlink 的 st-util, 不会退出
+    script(
+        "st-util",
+        "echo run >> \"$(dirname \"$0\")/util-runs\"\necho usage >&2\nsleep 30",
+    );
+
+    let path = std::env::join_paths(
+        std::iter::once(bin.clone())
+            .chain(std::env::split_paths(&std::env::var_os("PATH").unwrap())),
+    )
+    .unwrap();
+    let doctor = || {
+        let o = Command::new(env!("CARGO_BIN_EXE_st"))
+            .arg("doctor")
+            .current_dir(&dir)
+            .env("PATH", &path)
+            .env("XDG_CACHE_HOME", dir.join("cache"))
+            .output()
+            .unwrap();
+        assert!(o.status.success(), "{:?}", o);
+        assert!(!String::from_utf8_lossy(&o.stderr).contains("usage"));
+        let out = stdout(&o);
+        let header = out.lines().next().unwrap().to_string();
+        assert!(header.contains("good"), "{}", out);
+        assert!(!header.contains("util"), "{}", out);
+    };
+
+    let start = std::time::Instant::now();
+    doctor();
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    // 握手的结果已经保存, 不会再次执行 st-util
+    doctor();
+    let runs = fs::read_to_string(bin.join("util-runs")).unwrap();
+    assert_eq!(runs.lines().count(), 1, "{}", runs);
+}
+
+/// 在 `dir` 中执行 git
+fn git(dir: &Path, args: &[&str]) {
+    let o = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(o.status.success(), "{:?}", o);
+}
+
+#[test]
+fn hooks_install_chains_existing_hook() {
+    let dir = temp_dir("hooks");
+    git(&dir, &["init", "-q"]);
+    let hooks = dir.join(".git").join("hooks");
+    let user_hook = "#!/bin/sh\necho user hook\n";
+    write(&hooks.join("pre-commit"), user_hook);
+
+    let o = st(&dir, &["hooks", "install"]);
+    assert!(o.status.success(), "{:?}", o);
+    // 原有的钩子保存为 .local, 由 st 的钩子先执行
+    assert_eq!(
+        fs::read_to_string(hooks.join("pre-commit.local")).unwrap(),
+        user_hook
+    );
+    let script = fs::read_to_string(hooks.join("pre-commit")).unwrap();
+    assert!(script.contains("pre-commit.local"), "{}", script);
+    assert!(script.contains("hooks run pre-commit"), "{}", script);
+    assert!(hooks.join("pre-push").is_file());
+    assert!(!hooks.join("pre-push.local").exists());
+
+    // 重复安装 不会覆盖 .local
+    let o = st(&dir, &["hooks", "install"]);
+    assert!(o.status.success(), "{:?}", o);
+    assert_eq!(
+        fs::read_to_string(hooks.join("pre-commit.local")).unwrap(),
+        user_hook
+    );
+
+    // 卸载时 恢复原有的钩子
+    let o = st(&dir, &["hooks", "uninstall"]);
+    assert!(o.status.success(), "{:?}", o);
+    assert_eq!(
+        fs::read_to_string(hooks.join("pre-commit")).unwrap(),
+        user_hook
+    );
+    assert!(!hooks.join("pre-commit.local").exists());
+    assert!(!hooks.join("pre-push").exists());
+}
+
+#[test]
+fn hooks_install_refuses_existing_local() {
+    let dir = temp_dir("hooks_local");
+    git(&dir, &["init", "-q"]);
+    let hooks = dir.join(".git").join("hooks");
+    write(&hooks.join("pre-commit"), "#!/bin/sh\necho user hook\n");
+    write(
+        &hooks.join("pre-commit.local"),
+        "#!/bin/sh\necho older hook\n",
+    );
+
+    let o = st(&dir, &["hooks", "install"]);
+    assert!(!o.status.success(), "{:?}", o);
+    // 两个钩子都保持不变
+    assert_eq!(
+        fs::read_to_string(hooks.join("pre-commit")).unwrap(),
+        "#!/bin/sh\necho user hook\n"
+    );
+    assert_eq!(
+        fs::read_to_string(hooks.join("pre-commit.local")).unwrap(),
+        "#!/bin/sh\necho older hook\n"
+    );
+}
+
+#[test]
+fn hooks_run_in_sub_project() {
+    let dir = temp_dir("hooks_sub_project");
+    let backend = dir.join("backend");
+    git(&dir, &["init", "-q"]);
+    git(&dir, &["config", "user.email", "st@example.com"]);
+    git(&dir, &["config", "user.name", "st"]);
+    write(
+        &backend.join("Cargo.toml"),
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n",
+    );
+    // 钩子在项目目录中执行时 才会读取这个配置
+    write(
+        &backend.join("st.toml"),
+        "[hooks]\npre_format = \"false\"\n",
+    );
+
+    let o = st(&backend, &["hooks", "install"]);
+    assert!(o.status.success(), "{:?}", o);
+    git(&dir, &["add", "."]);
+    let o = Command::new("git")
+        .args(["commit", "-q", "-m", "test"])
+        .current_dir(&backend)
+        .output()
+        .unwrap();
+    assert!(!o.status.success(), "{:?}", o);
+    assert!(
+        String::from_utf8_lossy(&o.stderr).contains("pre_format"),
+        "{:?}",
+        o
+    );
+}
+
+#[test]
+fn hooks_skip_overridden_commands() {
+    let dir = temp_dir("hooks_override");
+    git(&dir, &["init", "-q"]);
+    write(
+        &dir.join("Cargo.toml"),
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n",
+    );
+    write(
+        &dir.join("st.toml"),
+        "[plugins.cargo]\nformat_check = \"cargo fmt --check\"\nlint = \"cargo clippy --all-targets -- -D warnings\"\n",
+    );
+    write(&dir.join("src").join("main.rs"), "fn main() {}\n");
+    git(&dir, &["add", "."]);
+
+    let o = st(&dir, &["--dry-run", "hooks", "run", "pre-commit"]);
+    assert!(o.status.success(), "{:?}", o);
+    // 覆盖后的格式检查 不能只检查暂存的文件, 跳过
+    assert!(
+        String::from_utf8_lossy(&o.stderr).contains("跳过"),
+        "{:?}",
+        o
+    );
+    // clippy 本身就检测整个项目
+    assert!(
+        stdout(&o).contains("clippy --all-targets -- -D warnings"),
+        "{:?}",
+        o
+    );
+}
+
+#[test]
+fn parallel_json_has_one_error() {
+    let dir = temp_dir("parallel_json");
+    write(
+        &dir.join("Cargo.toml"),
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n",
+    );
+    write(&dir.join("st.toml"), "[plugins.cargo]\ntest = \"false\"\n");
+
+    let o = st(&dir, &["-j", "2", "--output", "json", "test"]);
+    assert!(!o.status.success(), "{:?}", o);
+    let out = stdout(&o);
+    let count = |event: &str| out.matches(&format!("{{\"event\":\"{}\"", event)).count();
+    assert_eq!(count("error"), 1, "{}", out);
+    assert_eq!(count("action_start"), 1, "{}", out);
+    assert_eq!(count("action_finished"), 1, "{}", out);
+    assert_eq!(count("plugin_finished"), 1, "{}", out);
+    assert!(
+        out.trim_end().lines().last().unwrap().contains("\"error\""),
+        "{}",
+        out
+    );
+}
+
+#[test]
+fn unknown_command_lists_commands() {
+    let dir = temp_dir("unknown_command");
+    write(
+        &dir.join("Cargo.toml"),
+        "[package]\nname = \"a\"\nversion = \"0.1.0\"\n",
+    );
+    write(&dir.join("st.toml"), "[aliases]\ncheck = [\"test\"]\n");
+
+    let o = st(&dir, &["tset"]);
+    assert_eq!(o.status.code(), Some(2), "{:?}", o);
+    let stderr = String::from_utf8_lossy(&o.stderr);
+    assert!(stderr.contains("未知的命令: tset"), "{}", stderr);
+    for name in ["test", "format", "watch", "ci", "check"] {
+        assert!(stderr.contains(name), "{}", stderr);
+    }
+}
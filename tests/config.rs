@@ -0,0 +1,163 @@
+//! 配置文件的解析
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use st_cli::config::HookWhen;
+use st_cli::{StConfig, StError};
+
+use common::{temp_dir, TempDir};
+
+/// 写入 `st.toml` 的临时目录, 每个测试使用不同的名称
+fn config_dir(name: &str, content: &str) -> TempDir {
+    let dir = temp_dir(name);
+    fs::write(dir.join("st.toml"), content).unwrap();
+    dir
+}
+
+fn args(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn aliases() {
+    let dir = config_dir(
+        "aliases",
+        r#"
+[aliases]
+check = ["format --check", ["test", "--", "--nocapture"]]
+ci = ["test"]
+"#,
+    );
+    let config = StConfig::load(&dir).unwrap();
+    assert_eq!(
+        config.alias("check").unwrap(),
+        vec![
+            args(&["format", "--check"]),
+            args(&["test", "--", "--nocapture"])
+        ]
+    );
+    // 覆盖内置的别名
+    assert_eq!(config.alias("ci").unwrap(), vec![args(&["test"])]);
+    assert!(config.alias("deploy").is_none());
+}
+
+#[test]
+fn builtin_ci() {
+    let config = StConfig::default();
+    assert_eq!(
+        config.alias("ci").unwrap(),
+        vec![
+            args(&["format", "--check"]),
+            args(&["lint"]),
+            args(&["test"])
+        ]
+    );
+}
+
+#[test]
+fn invalid_alias() {
+    let dir = config_dir("invalid_alias", "[aliases]\ncheck = [\"\"]\n");
+    assert!(StConfig::load(&dir).is_err());
+}
+
+#[test]
+fn tasks() {
+    let dir = config_dir(
+        "tasks",
+        r#"
+[tasks]
+proto = "protoc --rust_out src api.proto"
+seed = ["python", "seed.py"]
+
+[tasks.docs]
+command = "mkdocs build"
+cwd = "docs"
+env = { SITE_URL = "https://example.com" }
+depends_on = ["proto"]
+
+[tasks.all]
+depends_on = ["docs", "seed", "proto"]
+"#,
+    );
+    let config = StConfig::load(&dir).unwrap();
+
+    let docs = &config.tasks["docs"];
+    assert_eq!(docs.command, Some(args(&["mkdocs", "build"])));
+    assert_eq!(docs.cwd, Some(PathBuf::from("docs")));
+    assert_eq!(docs.env["SITE_URL"], "https://example.com");
+    assert!(config.tasks["all"].command.is_none());
+
+    // 依赖在前, 每个任务只执行一次
+    assert_eq!(
+        config.task_plan("all").unwrap(),
+        vec!["proto", "docs", "seed", "all"]
+    );
+    assert_eq!(config.task_plan("proto").unwrap(), vec!["proto"]);
+    assert!(matches!(
+        config.task_plan("deploy"),
+        Err(StError::UnknownTask(_))
+    ));
+}
+
+#[test]
+fn task_cycle() {
+    let dir = config_dir(
+        "task_cycle",
+        r#"
+[tasks.a]
+command = "echo a"
+depends_on = ["b"]
+
+[tasks.b]
+command = "echo b"
+depends_on = ["c"]
+
+[tasks.c]
+depends_on = ["a"]
+
+[tasks.d]
+depends_on = ["missing"]
+"#,
+    );
+    let config = StConfig::load(&dir).unwrap();
+
+    match config.task_plan("a") {
+        Err(e @ StError::BadManifest { .. }) => {
+            assert!(e.to_string().contains("a -> b -> c -> a"), "{}", e)
+        }
+        other => panic!("{:?}", other),
+    }
+    assert!(matches!(
+        config.task_plan("d"),
+        Err(StError::BadManifest { .. })
+    ));
+}
+
+#[test]
+fn hooks() {
+    let dir = config_dir(
+        "hooks",
+        r#"
+[hooks]
+pre_build = "cargo sqlx prepare"
+post_publish = { command = ["notify-send", "done"], when = "always" }
+post_test = { command = "echo failed", when = "failure" }
+"#,
+    );
+    let config = StConfig::load(&dir).unwrap();
+
+    let pre = config.pre_hook("build").unwrap();
+    assert_eq!(pre.command, args(&["cargo", "sqlx", "prepare"]));
+    assert!(config.post_hook("build").is_none());
+
+    let publish = config.post_hook("publish").unwrap();
+    assert_eq!(publish.when, HookWhen::Always);
+    assert!(publish.when.matches(true) && publish.when.matches(false));
+
+    let test = config.post_hook("test").unwrap();
+    assert!(!test.when.matches(true) && test.when.matches(false));
+    assert!(HookWhen::default().matches(true) && !HookWhen::default().matches(false));
+}
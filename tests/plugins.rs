@@ -0,0 +1,678 @@
+//! 内置插件的识别 以及生成的命令行
+//!
+//! 使用 `RecordingRunner` 记录命令, 不会实际执行, 也不要求安装 cargo、npm、poetry
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use st_cli::public::bump::Bump;
+use st_cli::public::Invocation;
+use st_cli::{
+    run_action, Action, CommandRunner, Context, RecordingRunner, Registry, StConfig, StError,
+    StResult, StTrait,
+};
+
+use common::temp_dir;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+fn context(dir: &Path, runner: RecordingRunner) -> (Context, Arc<RecordingRunner>) {
+    let runner = Arc::new(runner);
+    let ctx = Context::new(dir).with_runner(runner.clone());
+    (ctx, runner)
+}
+
+/// 在 `dir` 中执行 `action`, 返回每个插件的执行结果 以及记录的命令行
+fn run_with(
+    dir: &Path,
+    runner: RecordingRunner,
+    action: Action,
+    args: &[&str],
+) -> (Result<(), StError>, Vec<String>) {
+    let (ctx, runner) = context(dir, runner);
+    let ctx = ctx.with_args(args.iter().map(|s| s.to_string()).collect());
+    let config = StConfig::load(dir).unwrap();
+    let ret = Registry::builtin()
+        .run(&config, &action, &ctx)
+        .into_result();
+    (ret, runner.command_lines())
+}
+
+fn run(dir: &Path, action: Action) -> Vec<String> {
+    let (ret, lines) = run_with(dir, RecordingRunner::new(), action, &[]);
+    assert!(ret.is_ok(), "{:?}", ret);
+    lines
+}
+
+fn detect(dir: &Path, runner: RecordingRunner) -> Vec<String> {
+    let (ctx, _) = context(dir, runner);
+    Registry::builtin().detect(&ctx)
+}
+
+#[test]
+fn detect_projects() {
+    let all = || RecordingRunner::new();
+    assert_eq!(detect(&fixture("cargo"), all()), vec!["cargo"]);
+    assert_eq!(detect(&fixture("npm"), all()), vec!["npm"]);
+    assert_eq!(detect(&fixture("poetry"), all()), vec!["poetry"]);
+    assert_eq!(detect(&fixture("django"), all()), vec!["poetry", "django"]);
+    assert!(detect(&fixture("pyproject_no_poetry"), all()).is_empty());
+    assert!(detect(&fixture("pyproject_invalid"), all()).is_empty());
+    assert!(detect(&temp_dir("empty"), all()).is_empty());
+}
+
+#[test]
+fn detect_requires_poetry() {
+    let runner = RecordingRunner::new().without_tool("poetry");
+    assert!(detect(&fixture("poetry"), runner).is_empty());
+}
+
+#[test]
+fn cargo_commands() {
+    let dir = fixture("cargo");
+    let cases = vec![
+        (Action::Build, "cargo build"),
+        (Action::Clean, "cargo clean"),
+        (Action::Format { check: false }, "cargo fmt"),
+        (
+            Action::Format { check: true },
+            "cargo fmt --check --message-format short",
+        ),
+        (Action::Lint, "cargo clippy"),
+        (Action::Outdated, "cargo outdated"),
+        (Action::Run, "cargo run"),
+        (Action::Update, "cargo update"),
+        (Action::Test, "cargo test"),
+        (Action::Install, "cargo install --force --path ."),
+    ];
+    for (action, line) in cases {
+        assert_eq!(run(&dir, action), vec![line]);
+    }
+
+    for action in [
+        Action::Sync,
+        Action::Lock,
+        Action::Publish,
+        Action::Bump(Bump::Dev),
+    ] {
+        let (ret, lines) = run_with(&dir, RecordingRunner::new(), action, &[]);
+        assert!(matches!(ret, Err(StError::NoHandler)));
+        assert!(lines.is_empty());
+    }
+}
+
+#[test]
+fn cargo_extra_args() {
+    let (ret, lines) = run_with(
+        &fixture("cargo"),
+        RecordingRunner::new(),
+        Action::Test,
+        &["--", "--nocapture"],
+    );
+    assert!(ret.is_ok());
+    assert_eq!(lines, vec!["cargo test -- --nocapture"]);
+}
+
+#[test]
+fn cargo_missing_tool() {
+    let (ret, lines) = run_with(
+        &fixture("cargo"),
+        RecordingRunner::new().without_tool("cargo"),
+        Action::Build,
+        &[],
+    );
+    assert!(matches!(ret, Err(StError::MissingTool(ref t)) if t == "cargo"));
+    assert!(lines.is_empty());
+}
+
+#[test]
+fn cargo_config_override() {
+    let dir = fixture("cargo_config");
+    assert_eq!(run(&dir, Action::Test), vec!["cargo test --release"]);
+    assert_eq!(
+        run(&dir, Action::Lint),
+        vec!["cargo clippy --all-targets -- -D warnings"]
+    );
+    assert_eq!(
+        run(&dir, Action::Format { check: false }),
+        vec!["cargo fmt --all"]
+    );
+    // 覆盖后的 format 会修改文件, 不能用来检查格式
+    let (ret, lines) = run_with(
+        &dir,
+        RecordingRunner::new(),
+        Action::Format { check: true },
+        &[],
+    );
+    assert!(matches!(ret, Err(StError::Unsupported { .. })), "{:?}", ret);
+    assert!(lines.is_empty());
+
+    assert_eq!(
+        run(
+            &fixture("cargo_format_check"),
+            Action::Format { check: true }
+        ),
+        vec!["cargo fmt --all --check"]
+    );
+}
+
+#[test]
+fn npm_commands() {
+    let dir = fixture("npm");
+    assert_eq!(run(&dir, Action::Clean), vec!["npm cache clean"]);
+    assert_eq!(run(&dir, Action::Outdated), vec!["npm outdated"]);
+    assert_eq!(run(&dir, Action::Update), vec!["npm update"]);
+
+    for action in [Action::Build, Action::Format { check: true }] {
+        let (ret, _) = run_with(&dir, RecordingRunner::new(), action, &[]);
+        assert!(matches!(ret, Err(StError::NoHandler)));
+    }
+}
+
+#[test]
+fn npm_prettier() {
+    let dir = fixture("npm_prettier");
+    // 只用 prettier 检查格式, 不会改写文件
+    let (ret, lines) = run_with(
+        &dir,
+        RecordingRunner::new(),
+        Action::Format { check: false },
+        &[],
+    );
+    assert!(matches!(ret, Err(StError::NoHandler)), "{:?}", ret);
+    assert!(lines.is_empty());
+    assert_eq!(
+        run(&dir, Action::Format { check: true }),
+        vec!["npm exec -- prettier --check ."]
+    );
+}
+
+#[test]
+fn poetry_commands() {
+    let dir = fixture("poetry");
+    let cases = vec![
+        (Action::Build, "poetry build"),
+        (Action::Format { check: false }, "poetry run black my_lib"),
+        (
+            Action::Format { check: true },
+            "poetry run black --check --diff my_lib",
+        ),
+        (Action::Outdated, "poetry show -o"),
+        (Action::Update, "poetry update"),
+        (Action::Lint, "poetry run pylama my_lib"),
+        (Action::Test, "poetry run pytest my_lib"),
+        (
+            Action::Lock,
+            "poetry export --without-hashes -f requirements.txt -o requirements.txt",
+        ),
+        (Action::Publish, "poetry publish"),
+    ];
+    for (action, line) in cases {
+        assert_eq!(run(&dir, action), vec![line]);
+    }
+
+    let (ret, _) = run_with(&dir, RecordingRunner::new(), Action::Run, &[]);
+    assert!(matches!(ret, Err(StError::NoHandler)));
+}
+
+#[test]
+fn poetry_requires_tools() {
+    let dir = fixture("poetry");
+    for (action, tool) in [
+        (Action::Format { check: false }, "black"),
+        (Action::Format { check: true }, "black"),
+        (Action::Lint, "pylama"),
+        (Action::Test, "pytest"),
+    ] {
+        let runner = RecordingRunner::new().without_tool(tool);
+        let (ret, lines) = run_with(&dir, runner, action, &[]);
+        assert!(matches!(ret, Err(StError::NoHandler)));
+        assert!(lines.is_empty());
+    }
+}
+
+#[test]
+fn django_run_server() {
+    let dir = fixture("django");
+    let (ctx, runner) = context(&dir, RecordingRunner::new());
+    let ctx = ctx.with_args(vec!["0.0.0.0:8000".to_string()]);
+    let ret = Registry::builtin()
+        .run(&StConfig::default(), &Action::Run, &ctx)
+        .into_result();
+    assert!(ret.is_ok(), "{:?}", ret);
+
+    assert_eq!(
+        runner.command_lines(),
+        vec![
+            "poetry run python manage.py makemigrations",
+            "poetry run python manage.py migrate",
+            "poetry run python manage.py runserver 0.0.0.0:8000",
+        ]
+    );
+    for cmd in runner.commands() {
+        assert_eq!(cmd.cwd, dir.join("my_site"));
+        assert_eq!(cmd.envs["DJANGO_SETTINGS_MODULE"], "my_site.settings");
+        assert_eq!(cmd.envs["DJANGO_DEV"], "1");
+        assert!(!cmd.envs.contains_key("DJANGO_PROD"));
+    }
+}
+
+#[test]
+fn django_lint() {
+    let dir = fixture("django");
+    let (ctx, runner) = context(&dir, RecordingRunner::new());
+    let ret = Registry::builtin()
+        .run(&StConfig::default(), &Action::Lint, &ctx)
+        .into_result();
+    assert!(ret.is_ok(), "{:?}", ret);
+
+    let commands = runner.commands();
+    assert_eq!(
+        runner.command_lines(),
+        vec![
+            "poetry run pylama my_site",
+            "poetry run python manage.py check",
+        ]
+    );
+    // django 的环境变量 不会影响其他插件
+    assert_eq!(commands[0].cwd, dir);
+    assert!(commands[0].envs.is_empty());
+    assert_eq!(commands[1].cwd, dir.join("my_site"));
+}
+
+#[test]
+fn django_bump() {
+    let dir = temp_dir("bump");
+    let src = fixture("django");
+    fs::copy(src.join("pyproject.toml"), dir.join("pyproject.toml")).unwrap();
+    fs::copy(src.join("version.json"), dir.join("version.json")).unwrap();
+    fs::create_dir_all(dir.join("my_site").join("my_site")).unwrap();
+    fs::write(dir.join("my_site").join("my_site").join("wsgi.py"), "").unwrap();
+
+    assert!(run(&dir, Action::Bump(Bump::Test)).is_empty());
+
+    let v: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("version.json")).unwrap()).unwrap();
+    assert_eq!(v["test"]["old"], "0.1.0");
+    assert_eq!(v["test"]["new"], "0.2.0");
+    assert_eq!(v["dev"]["new"], "0.1.0");
+}
+
+#[test]
+fn dry_run_does_not_run() {
+    let (ctx, runner) = context(&fixture("cargo"), RecordingRunner::new());
+    let ctx = Context {
+        dry_run: true,
+        ..ctx
+    };
+    let ret = Registry::builtin()
+        .run(&StConfig::default(), &Action::Build, &ctx)
+        .into_result();
+    assert!(ret.is_ok());
+    assert!(runner.commands().is_empty());
+}
+
+/// 只处理 `files` 中的文件时, 执行 `action` 记录的命令行
+fn run_files(dir: &Path, action: Action, files: &[&str]) -> Vec<String> {
+    let (ctx, runner) = context(dir, RecordingRunner::new());
+    let ctx = Context {
+        files: Some(files.iter().map(PathBuf::from).collect()),
+        ..ctx
+    };
+    let ret = Registry::builtin()
+        .run(&StConfig::default(), &action, &ctx)
+        .into_result();
+    assert!(ret.is_ok(), "{:?}", ret);
+    runner.command_lines()
+}
+
+#[test]
+fn changed_files_with_override() {
+    let (ctx, runner) = context(&fixture("cargo_config"), RecordingRunner::new());
+    let ctx = Context {
+        files: Some(vec![PathBuf::from("src/main.rs")]),
+        ..ctx
+    };
+    let config = StConfig::load(&fixture("cargo_config")).unwrap();
+    let ret = Registry::builtin()
+        .run(&config, &Action::Format { check: false }, &ctx)
+        .into_result();
+    assert!(matches!(ret, Err(StError::Unsupported { .. })), "{:?}", ret);
+    assert!(runner.commands().is_empty());
+
+    // clippy 本身只能检测整个项目, 覆盖后的命令 同样检测整个项目
+    let ret = Registry::builtin()
+        .run(&config, &Action::Lint, &ctx)
+        .into_result();
+    assert!(ret.is_ok(), "{:?}", ret);
+    assert_eq!(
+        runner.command_lines(),
+        vec!["cargo clippy --all-targets -- -D warnings"]
+    );
+}
+
+#[test]
+fn cargo_changed_files() {
+    let dir = fixture("cargo");
+    let files = ["src/main.rs", "README.md", "src/lib.rs"];
+    assert_eq!(
+        run_files(&dir, Action::Format { check: false }, &files),
+        vec!["rustfmt --edition 2018 src/main.rs src/lib.rs"]
+    );
+    assert_eq!(
+        run_files(&dir, Action::Format { check: true }, &files),
+        vec!["rustfmt --edition 2018 --check -l src/main.rs src/lib.rs"]
+    );
+    assert_eq!(run_files(&dir, Action::Lint, &files), vec!["cargo clippy"]);
+
+    // 没有改动 Rust 文件时 跳过
+    assert!(run_files(&dir, Action::Format { check: true }, &["README.md"]).is_empty());
+    assert!(run_files(&dir, Action::Lint, &[]).is_empty());
+    // 其他命令 不受影响
+    assert_eq!(run_files(&dir, Action::Test, &[]), vec!["cargo test"]);
+}
+
+#[test]
+fn poetry_changed_files() {
+    let dir = fixture("poetry");
+    let files = ["my_lib/a.py", "pyproject.toml", "tests/test_a.py"];
+    assert_eq!(
+        run_files(&dir, Action::Format { check: true }, &files),
+        vec!["poetry run black --check --diff my_lib/a.py tests/test_a.py"]
+    );
+    assert_eq!(
+        run_files(&dir, Action::Lint, &files),
+        vec!["poetry run pylama my_lib/a.py tests/test_a.py"]
+    );
+    assert!(run_files(&dir, Action::Lint, &["pyproject.toml"]).is_empty());
+    assert!(run_files(&fixture("django"), Action::Lint, &["README.md"]).is_empty());
+}
+
+#[test]
+fn npm_changed_files() {
+    let dir = fixture("npm_prettier");
+    assert_eq!(
+        run_files(
+            &dir,
+            Action::Format { check: true },
+            &["src/app.tsx", "logo.png", "package.json"]
+        ),
+        vec!["npm exec -- prettier --check src/app.tsx package.json"]
+    );
+    assert!(run_files(&dir, Action::Format { check: true }, &["logo.png"]).is_empty());
+}
+
+#[test]
+fn cache_skips_unchanged_runs() {
+    let dir = temp_dir("cache");
+    fs::copy(fixture("cargo").join("Cargo.toml"), dir.join("Cargo.toml")).unwrap();
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+    let config = StConfig {
+        cache: true,
+        ..StConfig::default()
+    };
+    let lint = |args: &[&str]| {
+        let (ctx, runner) = context(&dir, RecordingRunner::new());
+        let ctx = ctx.with_args(args.iter().map(|s| s.to_string()).collect());
+        let ret = Registry::builtin()
+            .run(&config, &Action::Lint, &ctx)
+            .into_result();
+        assert!(ret.is_ok(), "{:?}", ret);
+        runner.command_lines()
+    };
+
+    assert_eq!(lint(&[]), vec!["cargo clippy"]);
+    assert!(lint(&[]).is_empty());
+    assert!(dir.join(".st").join("cache").join("cargo-lint").is_file());
+    // 命令行不同
+    assert_eq!(lint(&["--all-targets"]), vec!["cargo clippy --all-targets"]);
+    // 源代码改动了
+    fs::write(dir.join("src").join("main.rs"), "fn main() { }\n").unwrap();
+    assert_eq!(lint(&[]), vec!["cargo clippy"]);
+    assert!(lint(&[]).is_empty());
+    // 隐藏的配置文件改动了
+    fs::write(dir.join(".clippy.toml"), "msrv = \"1.60\"\n").unwrap();
+    assert_eq!(lint(&[]), vec!["cargo clippy"]);
+    assert!(lint(&[]).is_empty());
+    // 生成的目录 不影响缓存
+    fs::create_dir_all(dir.join("target")).unwrap();
+    fs::write(dir.join("target").join("out"), "").unwrap();
+    assert!(lint(&[]).is_empty());
+    // 指向目录的符号链接 以及 emacs 的锁文件 (指向不存在的文件)
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+        symlink("src", dir.join("link")).unwrap();
+        symlink("user@host.1234", dir.join(".#main.rs")).unwrap();
+        assert_eq!(lint(&[]), vec!["cargo clippy"]);
+        assert!(lint(&[]).is_empty());
+    }
+    // clean 之后 删除缓存
+    let (ctx, _) = context(&dir, RecordingRunner::new());
+    assert!(Registry::builtin()
+        .run(&config, &Action::Clean, &ctx)
+        .is_success());
+    assert!(!dir.join(".st").join("cache").exists());
+    assert_eq!(lint(&[]), vec!["cargo clippy"]);
+    // 不会缓存修改源代码的命令
+    let (ctx, runner) = context(&dir, RecordingRunner::new());
+    let format = Action::Format { check: false };
+    for _ in 0..2 {
+        assert!(Registry::builtin().run(&config, &format, &ctx).is_success());
+    }
+    assert_eq!(runner.command_lines(), vec!["cargo fmt", "cargo fmt"]);
+}
+
+#[test]
+fn cache_tracks_workspace_and_path_dependencies() {
+    let workspace = temp_dir("cache_workspace");
+    let write = |path: &str, content: &str| {
+        let path = workspace.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    };
+    write("Cargo.toml", "[workspace]\nmembers = [\"app\"]\n");
+    write("Cargo.lock", "version = 3\n");
+    write(
+        "app/Cargo.toml",
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\ncore = { path = \"../core\" }\n",
+    );
+    write("app/src/main.rs", "fn main() {}\n");
+    write(
+        "core/Cargo.toml",
+        "[package]\nname = \"core\"\nversion = \"0.1.0\"\n",
+    );
+    write("core/src/lib.rs", "");
+    let config = StConfig {
+        cache: true,
+        ..StConfig::default()
+    };
+    let lint = || {
+        let (ctx, runner) = context(&workspace.join("app"), RecordingRunner::new());
+        let ret = Registry::builtin()
+            .run(&config, &Action::Lint, &ctx)
+            .into_result();
+        assert!(ret.is_ok(), "{:?}", ret);
+        runner.command_lines()
+    };
+
+    assert_eq!(lint(), vec!["cargo clippy"]);
+    assert!(lint().is_empty());
+    // workspace 的锁文件
+    write("Cargo.lock", "version = 4\n");
+    assert_eq!(lint(), vec!["cargo clippy"]);
+    assert!(lint().is_empty());
+    // 项目外的 path 依赖
+    write("core/src/lib.rs", "pub fn f() {}\n");
+    assert_eq!(lint(), vec!["cargo clippy"]);
+    assert!(lint().is_empty());
+}
+
+#[test]
+fn outcome_records_commands() {
+    let dir = fixture("cargo_config");
+    let (ctx, _) = context(&dir, RecordingRunner::new());
+    let report = Registry::builtin().run(&StConfig::load(&dir).unwrap(), &Action::Lint, &ctx);
+    assert_eq!(report.outcomes.len(), 1);
+
+    let outcome = &report.outcomes[0];
+    assert_eq!(outcome.plugin, "cargo");
+    assert!(!outcome.cached);
+    let lines: Vec<String> = outcome.commands.iter().map(|c| c.to_string()).collect();
+    assert_eq!(lines, vec!["cargo clippy --all-targets -- -D warnings"]);
+    assert_eq!(outcome.status(), "pass");
+}
+
+#[test]
+fn dry_run_outcome_records_commands() {
+    let dir = fixture("cargo_config");
+    let (ctx, runner) = context(&dir, RecordingRunner::new());
+    let ctx = Context {
+        dry_run: true,
+        ..ctx
+    };
+    let report = Registry::builtin().run(&StConfig::load(&dir).unwrap(), &Action::Lint, &ctx);
+    assert_eq!(report.outcomes.len(), 1);
+
+    let outcome = &report.outcomes[0];
+    assert_eq!(outcome.status(), "dry-run");
+    let lines: Vec<String> = outcome.commands.iter().map(|c| c.to_string()).collect();
+    assert_eq!(lines, vec!["cargo clippy --all-targets -- -D warnings"]);
+    // 没有实际执行
+    assert!(runner.command_lines().is_empty());
+}
+
+/// 在配置了钩子的 cargo 项目中执行 `action`, 返回结果 以及记录的命令行
+fn run_hooked(
+    name: &str,
+    hooks: &str,
+    runner: RecordingRunner,
+) -> (Result<(), StError>, Vec<String>) {
+    let dir = temp_dir(name);
+    fs::copy(fixture("cargo").join("Cargo.toml"), dir.join("Cargo.toml")).unwrap();
+    fs::write(dir.join("st.toml"), format!("[hooks]\n{}", hooks)).unwrap();
+    let (ctx, runner) = context(&dir, runner);
+    let config = StConfig::load(&dir).unwrap();
+
+    let ret = run_action(&Registry::builtin(), &config, &Action::Build, &ctx);
+    (ret, runner.command_lines())
+}
+
+#[test]
+fn pre_hook_failure_skips_plugins() {
+    let hooks =
+        "pre_build = \"make prepare\"\npost_build = { command = \"notify\", when = \"always\" }\n";
+    let (ret, lines) = run_hooked("pre_hook", hooks, RecordingRunner::new());
+    assert!(ret.is_ok(), "{:?}", ret);
+    assert_eq!(lines, vec!["make prepare", "cargo build", "notify"]);
+
+    let runner = RecordingRunner::new().with_failure("make prepare");
+    let (ret, lines) = run_hooked("pre_hook_failure", hooks, runner);
+    assert!(matches!(ret, Err(StError::ChildFailed { .. })), "{:?}", ret);
+    assert_eq!(lines, vec!["make prepare"]);
+}
+
+#[test]
+fn post_hook_failure_fails_result() {
+    let hooks = "post_build = \"notify\"\n";
+    let runner = RecordingRunner::new().with_failure("notify");
+    let (ret, lines) = run_hooked("post_hook_failure", hooks, runner);
+    assert!(
+        matches!(&ret, Err(StError::ChildFailed { command, .. }) if command.contains("notify")),
+        "{:?}",
+        ret
+    );
+    assert_eq!(lines, vec!["cargo build", "notify"]);
+
+    // 命令失败时 返回命令的错误, 默认不执行后置钩子
+    let runner = RecordingRunner::new().with_failure("cargo build");
+    let (ret, lines) = run_hooked("post_hook_skipped", hooks, runner);
+    assert!(
+        matches!(&ret, Err(StError::ChildFailed { command, .. }) if command.contains("cargo")),
+        "{:?}",
+        ret
+    );
+    assert_eq!(lines, vec!["cargo build"]);
+}
+
+#[test]
+fn post_hook_when_failure() {
+    let hooks = "post_build = { command = \"notify failed\", when = \"failure\" }\n";
+    let (ret, lines) = run_hooked("when_failure_pass", hooks, RecordingRunner::new());
+    assert!(ret.is_ok(), "{:?}", ret);
+    assert_eq!(lines, vec!["cargo build"]);
+
+    let runner = RecordingRunner::new().with_failure("cargo build");
+    let (ret, lines) = run_hooked("when_failure_fail", hooks, runner);
+    assert!(ret.is_err());
+    assert_eq!(lines, vec!["cargo build", "notify failed"]);
+}
+
+/// 只区分 `output` 和 `output_timeout` 的执行器
+#[derive(Debug)]
+struct TimeoutRunner;
+
+impl CommandRunner for TimeoutRunner {
+    fn which(&self, name: &str) -> Result<PathBuf, StError> {
+        Ok(PathBuf::from(name))
+    }
+
+    fn run(&self, _cmd: &Invocation) -> StResult {
+        Ok(())
+    }
+
+    fn check(&self, _cmd: &Invocation) -> bool {
+        true
+    }
+
+    fn output(&self, _cmd: &Invocation) -> Result<String, StError> {
+        Ok("output".to_string())
+    }
+
+    fn output_timeout(&self, _cmd: &Invocation, _timeout: Duration) -> Result<String, StError> {
+        Ok("output_timeout".to_string())
+    }
+}
+
+/// 执行时 使用 `output_timeout` 查询, 保存查询的结果
+struct QueryPlugin(Arc<Mutex<String>>);
+
+impl StTrait for QueryPlugin {
+    fn name(&self) -> String {
+        "query".to_string()
+    }
+
+    fn supports(&self, _action: &Action, _ctx: &Context) -> bool {
+        true
+    }
+
+    fn run(&self, _action: &Action, ctx: &Context) -> StResult {
+        let out = ctx.output_timeout("tool".into(), vec![], Duration::from_secs(1))?;
+        *self.0.lock().unwrap() = out;
+        Ok(())
+    }
+}
+
+#[test]
+fn run_keeps_output_timeout() {
+    let seen = Arc::new(Mutex::new(String::new()));
+    let mut registry = Registry::new();
+    registry.register(QueryPlugin(seen.clone()));
+    let ctx = Context::new(".").with_runner(Arc::new(TimeoutRunner));
+
+    let ret = registry
+        .run(&StConfig::default(), &Action::Test, &ctx)
+        .into_result();
+    assert!(ret.is_ok(), "{:?}", ret);
+    assert_eq!(*seen.lock().unwrap(), "output_timeout");
+}